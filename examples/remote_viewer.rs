@@ -0,0 +1,30 @@
+//! Shows the shapes streamed by an app with a `RemoteSink`:
+//!
+//! ```ignore
+//! let mut sink = RemoteSink::default();
+//! sink.listen("127.0.0.1:7878").unwrap();
+//! app.add_resource(sink);
+//! ```
+//!
+//! Run with `cargo run --example remote_viewer --features remote -- <address>`.
+
+use bevy::prelude::*;
+use bevy_prototype_2d_graphics::{Immediate2DGraphicsPlugin, RemoteSource};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+fn connect(mut source: ResMut<RemoteSource>) {
+    let address = std::env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    if let Err(error) = source.connect(address.as_str()) {
+        eprintln!("could not connect to {}: {}", address, error);
+    }
+}
+
+fn main() {
+    let mut app = App::build();
+    app
+        .add_default_plugins()
+        .add_plugin(Immediate2DGraphicsPlugin)
+        .add_startup_system(connect.system())
+        .run();
+}
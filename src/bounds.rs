@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+use super::transform;
+
+/// Axis aligned rectangle, e.g. to clip shapes with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Bounds {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self {
+            min,
+            max,
+        }
+    }
+
+    pub fn from_center_size(center: Vec2, size: Vec2) -> Self {
+        Self {
+            min: center - size / 2.,
+            max: center + size / 2.,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x() >= self.min.x() && point.x() <= self.max.x()
+            && point.y() >= self.min.y() && point.y() <= self.max.y()
+    }
+
+    /// Overlapping part of both bounds, empty bounds have a negative size.
+    pub fn intersection(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    /// Smallest bounds containing both bounds.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Bounds grown by an amount on every side.
+    pub(crate) fn expanded(&self, amount: f32) -> Bounds {
+        Bounds {
+            min: self.min - Vec2::splat(amount),
+            max: self.max + Vec2::splat(amount),
+        }
+    }
+
+    /// Whether the bounds contain no area, e.g. when they are an empty intersection.
+    pub fn is_empty(&self) -> bool {
+        self.min.x() > self.max.x() || self.min.y() > self.max.y()
+    }
+
+    /// Bounds of the transformed corners.
+    pub(crate) fn transformed(&self, affine: &Mat3) -> Bounds {
+        let corners = [
+            transform::transform_point(affine, self.min),
+            transform::transform_point(affine, Vec2::new(self.max.x(), self.min.y())),
+            transform::transform_point(affine, self.max),
+            transform::transform_point(affine, Vec2::new(self.min.x(), self.max.y())),
+        ];
+
+        let mut bounds = Bounds::new(corners[0], corners[0]);
+        for corner in corners.iter().skip(1) {
+            bounds.min = bounds.min.min(*corner);
+            bounds.max = bounds.max.max(*corner);
+        }
+        bounds
+    }
+}
+
+/// Clip rectangle as passed to the shaders, `(min.x, min.y, max.x, max.y)` in world space.
+pub(crate) fn clip_uniform(clip: &Option<Bounds>) -> Vec4 {
+    if let Some(clip) = clip {
+        Vec4::new(clip.min.x(), clip.min.y(), clip.max.x(), clip.max.y())
+    } else {
+        Vec4::new(f32::MIN, f32::MIN, f32::MAX, f32::MAX)
+    }
+}
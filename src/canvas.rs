@@ -0,0 +1,249 @@
+use bevy::{
+    render::{
+        camera::{ActiveCameras, Camera, CameraProjection, OrthographicProjection},
+        pass::{
+            LoadOp, PassDescriptor, RenderPassColorAttachmentDescriptor,
+            RenderPassDepthStencilAttachmentDescriptor, StoreOp, TextureAttachment,
+        },
+        render_graph::{
+            base, CameraNode, Node, PassNode, RenderGraph, ResourceSlotInfo, ResourceSlots,
+            TextureNode,
+        },
+        renderer::{RenderContext, RenderResourceId, RenderResourceType},
+        texture::{
+            Extent3d, SamplerDescriptor, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureId, TextureUsage, SAMPLER_ASSET_INDEX, TEXTURE_ASSET_INDEX,
+        },
+    },
+    window::WindowId,
+    prelude::*,
+};
+use std::borrow::Cow;
+use super::scope::ScopedGraphics;
+use super::{circles, rectangles, lines, images, textures};
+
+/// Marks entities drawn by the offscreen passes of canvases.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CanvasPass;
+
+/// Camera rendering the shapes of a canvas.
+#[derive(Debug)]
+pub struct CanvasCamera {
+    pub texture: Handle<Texture>,
+}
+
+/// Graphics whose shapes are drawn into a canvas instead of the main pass.
+/// Restores the previous target when dropped.
+pub type CanvasGraphics<'a> = ScopedGraphics<'a, Option<Handle<Texture>>>;
+
+const CANVAS_TEXTURE_FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
+
+/// Creates the render target of a canvas and registers it as the
+/// gpu resource of its texture handle, so it can be used like any other texture.
+pub struct CanvasTextureNode {
+    texture: Handle<Texture>,
+    descriptor: TextureDescriptor,
+    texture_id: Option<TextureId>,
+}
+
+impl CanvasTextureNode {
+    pub const TEXTURE: &'static str = "texture";
+
+    pub fn new(texture: Handle<Texture>, width: u32, height: u32) -> Self {
+        Self {
+            texture,
+            descriptor: TextureDescriptor {
+                size: Extent3d::new(width, height, 1),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: CANVAS_TEXTURE_FORMAT,
+                usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED,
+            },
+            texture_id: None,
+        }
+    }
+}
+
+impl Node for CanvasTextureNode {
+    fn output(&self) -> &[ResourceSlotInfo] {
+        static OUTPUT: &[ResourceSlotInfo] = &[ResourceSlotInfo {
+            name: Cow::Borrowed(CanvasTextureNode::TEXTURE),
+            resource_type: RenderResourceType::Texture,
+        }];
+        OUTPUT
+    }
+
+    fn update(
+        &mut self,
+        _world: &World,
+        _resources: &Resources,
+        render_context: &mut dyn RenderContext,
+        _input: &ResourceSlots,
+        output: &mut ResourceSlots,
+    ) {
+        let texture_id = if let Some(texture_id) = self.texture_id {
+            texture_id
+        } else {
+            let render_resource_context = render_context.resources_mut();
+            let texture_id = render_resource_context.create_texture(self.descriptor);
+            let sampler_id = render_resource_context.create_sampler(&SamplerDescriptor::default());
+
+            render_resource_context.set_asset_resource(
+                self.texture,
+                RenderResourceId::Texture(texture_id),
+                TEXTURE_ASSET_INDEX,
+            );
+            render_resource_context.set_asset_resource(
+                self.texture,
+                RenderResourceId::Sampler(sampler_id),
+                SAMPLER_ASSET_INDEX,
+            );
+
+            self.texture_id = Some(texture_id);
+            texture_id
+        };
+
+        output.set(0, RenderResourceId::Texture(texture_id));
+    }
+}
+
+pub(crate) fn canvas_name(texture: Handle<Texture>, node: &str) -> String {
+    format!("canvas_{:?}_{}", texture.id, node)
+}
+
+/// Adds the offscreen pass of a canvas, rendered before the main pass.
+fn add_canvas_graph(resources: &Resources, texture: Handle<Texture>, width: u32, height: u32) -> String {
+    let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
+    let msaa = resources.get::<Msaa>().map(|msaa| msaa.samples).unwrap_or(1);
+
+    let camera = canvas_name(texture, "camera");
+    let pass = canvas_name(texture, "pass");
+    let target = canvas_name(texture, "target");
+    let multisampled = canvas_name(texture, "multisampled");
+    let depth = canvas_name(texture, "depth");
+
+    // shape pipelines are specialized for the sample count of the main pass,
+    // so canvases are multisampled the same way and resolved into their texture.
+    let color_attachment = if msaa > 1 {
+        RenderPassColorAttachmentDescriptor {
+            attachment: TextureAttachment::Input("color_attachment".to_string()),
+            resolve_target: Some(TextureAttachment::Input("color_resolve_target".to_string())),
+            load_op: LoadOp::Clear,
+            store_op: StoreOp::Store,
+            clear_color: Color::rgba(0., 0., 0., 0.),
+        }
+    } else {
+        RenderPassColorAttachmentDescriptor {
+            attachment: TextureAttachment::Input("color_attachment".to_string()),
+            resolve_target: None,
+            load_op: LoadOp::Clear,
+            store_op: StoreOp::Store,
+            clear_color: Color::rgba(0., 0., 0., 0.),
+        }
+    };
+
+    let mut pass_node = PassNode::<&CanvasPass>::new(PassDescriptor {
+        color_attachments: vec![color_attachment],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: TextureAttachment::Input("depth".to_string()),
+            depth_load_op: LoadOp::Clear,
+            depth_store_op: StoreOp::Store,
+            stencil_load_op: LoadOp::Clear,
+            stencil_store_op: StoreOp::Store,
+            clear_depth: 1.0,
+            clear_stencil: 0,
+            depth_read_only: false,
+            stencil_read_only: false,
+        }),
+        sample_count: msaa,
+    });
+    pass_node.add_camera(&camera);
+
+    render_graph.add_system_node(camera.clone(), CameraNode::new(camera.clone()));
+    render_graph.add_node(pass.clone(), pass_node);
+    render_graph.add_node(target.clone(), CanvasTextureNode::new(texture, width, height));
+    render_graph.add_node(depth.clone(), TextureNode::new(TextureDescriptor {
+        size: Extent3d::new(width, height, 1),
+        mip_level_count: 1,
+        sample_count: msaa,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Depth32Float,
+        usage: TextureUsage::OUTPUT_ATTACHMENT,
+    }));
+
+    if msaa > 1 {
+        render_graph.add_node(multisampled.clone(), TextureNode::new(TextureDescriptor {
+            size: Extent3d::new(width, height, 1),
+            mip_level_count: 1,
+            sample_count: msaa,
+            dimension: TextureDimension::D2,
+            format: CANVAS_TEXTURE_FORMAT,
+            usage: TextureUsage::OUTPUT_ATTACHMENT,
+        }));
+        render_graph.add_slot_edge(multisampled.as_str(), TextureNode::TEXTURE, pass.as_str(), "color_attachment").unwrap();
+        render_graph.add_slot_edge(target.as_str(), CanvasTextureNode::TEXTURE, pass.as_str(), "color_resolve_target").unwrap();
+    } else {
+        render_graph.add_slot_edge(target.as_str(), CanvasTextureNode::TEXTURE, pass.as_str(), "color_attachment").unwrap();
+    }
+    render_graph.add_slot_edge(depth.as_str(), TextureNode::TEXTURE, pass.as_str(), "depth").unwrap();
+
+    render_graph.add_node_edge(camera.as_str(), pass.as_str()).unwrap();
+    render_graph.add_node_edge(base::node::TRANSFORM, pass.as_str()).unwrap();
+    render_graph.add_node_edge(base::node::TEXTURE_COPY, pass.as_str()).unwrap();
+    for style_node in [
+        circles::CIRCLE_STYLE_NODE,
+        rectangles::RECTANGLE_STYLE_NODE,
+        lines::LINE_STYLE_NODE,
+        images::IMAGE_STYLE_NODE,
+        textures::SHAPE_TEXTURE_NODE,
+    ].iter() {
+        render_graph.add_node_edge(*style_node, pass.as_str()).unwrap();
+    }
+    render_graph.add_node_edge(pass.as_str(), base::node::MAIN_PASS).unwrap();
+
+    camera
+}
+
+pub trait AddCanvas {
+    /// Register a texture of the given size in pixels that shapes can be drawn into
+    /// with `Immediate2DGraphics::canvas`. The texture must not be added to `Assets<Texture>`.
+    /// The origin of the canvas is at its center, one world unit being one pixel.
+    /// Images of the canvas need a size set with `ImageBuilder::with_size`, as the texture
+    /// is not in `Assets<Texture>`.
+    fn add_canvas(&mut self, texture: Handle<Texture>, width: u32, height: u32) -> &mut Self;
+}
+
+impl AddCanvas for AppBuilder {
+    fn add_canvas(&mut self, texture: Handle<Texture>, width: u32, height: u32) -> &mut Self {
+        let camera_name = {
+            let resources = self.resources();
+            let camera_name = add_canvas_graph(&*resources, texture, width, height);
+
+            // the camera node only gets a view and visible entities for active cameras.
+            let mut active_cameras = resources.get_mut::<ActiveCameras>().unwrap();
+            active_cameras.add(&camera_name);
+            camera_name
+        };
+
+        let mut projection = OrthographicProjection::default();
+        projection.update(width as usize, height as usize);
+
+        // the camera does not belong to a window, so it keeps the size of the canvas.
+        let camera = Camera {
+            name: Some(camera_name),
+            window: WindowId::new(),
+            projection_matrix: projection.get_projection_matrix(),
+            ..Default::default()
+        };
+
+        let camera_entity = self.app.world.spawn(Camera2dComponents {
+            camera,
+            orthographic_projection: projection,
+            ..Default::default()
+        });
+        self.app.world.insert_one(camera_entity, CanvasCamera { texture }).unwrap();
+
+        self
+    }
+}
@@ -9,19 +9,45 @@ use bevy::{
         render_graph::{RenderGraph, base, RenderResourcesNode}
     },
     core::Byteable,
+    ecs::Without,
     sprite::QUAD_HANDLE,
     prelude::*,
 };
 use std::ops::{DerefMut, Deref};
+use std::time::Instant;
 use super::Immediate2DGraphics;
+use super::stats::Immediate2DGraphicsStats;
+use super::state::ShapeState;
+use super::textures::{self, ShapeTexture, TextureFit, UvTransform};
+use super::effects::{Effects, Shadow, Glow};
+use super::transform;
+use super::bounds;
+use super::lifetime;
+use super::canvas::CanvasPass;
+use super::contexts::ShapeTarget;
+use super::groups::RetainedShape;
+use super::pool::{PooledShape, ShapePool};
+use base::MainPass;
 
-#[derive(Debug, RenderResources, RenderResource)]
+#[derive(Debug, Clone, RenderResources, RenderResource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[render_resources(from_self)]
 #[allow(unused)]
+#[repr(C)]
 pub struct CircleStyle {
     pub fill_color: Color,
     pub border_color: Color,
+    pub uv_transform: Vec4,
+    pub uv_offset: Vec4,
+    pub shadow_color: Color,
+    pub glow_color: Color,
+    pub shadow: Vec4,
+    pub clip: Vec4,
     pub border_width: f32,
+    pub radius: f32,
+    /// Half the size of the quad, larger than the radius if there is a shadow or glow.
+    pub extent: f32,
+    pub glow_radius: f32,
 }
 unsafe impl Byteable for CircleStyle {}
 
@@ -30,26 +56,76 @@ impl Default for CircleStyle {
         Self {
             fill_color: Color::BLACK,
             border_color: Color::BLACK,
+            uv_transform: Vec4::new(1., 0., 0., 1.),
+            uv_offset: Vec4::zero(),
+            shadow_color: Color::rgba(0., 0., 0., 0.),
+            glow_color: Color::rgba(0., 0., 0., 0.),
+            shadow: Vec4::zero(),
+            clip: bounds::clip_uniform(&None),
             border_width: 0.,
+            radius: 1.,
+            extent: 1.,
+            glow_radius: 0.,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Circle {
+    pub state: ShapeState,
     pub style: CircleStyle,
     pub pos: Vec2,
     pub radius: f32,
+    pub texture: Option<Handle<Texture>>,
+    pub uv: UvTransform,
+    pub effects: Effects,
+    /// Width of the outline in world units, if only the outline is drawn.
+    pub outline: Option<f32>,
+    /// Whether the fill color was set. Textures are drawn untinted otherwise.
+    pub has_color: bool,
 }
 
 impl Circle {
-    pub fn new(x: f32, y: f32) -> Self {
+    pub fn new(state: ShapeState, x: f32, y: f32) -> Self {
         Self {
+            state,
             style: CircleStyle::default(),
             pos: Vec2::new(x, y),
             radius: 100.,
+            texture: None,
+            uv: UvTransform::default(),
+            effects: Effects::default(),
+            outline: None,
+            has_color: false,
         }
     }
+
+    pub fn new_outline(state: ShapeState, x: f32, y: f32) -> Self {
+        let mut circle = Self::new(state, x, y);
+        circle.style.fill_color = Color::rgba(0., 0., 0., 0.);
+        circle.outline = Some(1.);
+        circle
+    }
+
+    /// Bounds in the space the circle is transformed into, usually world units,
+    /// limited to its clip rectangle. Shadows and glows are not included.
+    pub fn bounds(&self) -> bounds::Bounds {
+        let affine = &self.state.transform;
+        let (x_axis, y_axis) = (affine.x_axis(), affine.y_axis());
+        // a transformed circle is an ellipse, whose extent on each axis follows from the matrix rows.
+        let half_size = Vec2::new(
+            Vec2::new(x_axis.x(), y_axis.x()).length(),
+            Vec2::new(x_axis.y(), y_axis.y()).length(),
+        ) * self.radius;
+        let center = transform::transform_point(affine, self.pos);
+        self.state.clipped(bounds::Bounds::new(center - half_size, center + half_size))
+    }
+
+    /// Maps the space of the circle, centered on it but not scaled by its radius, to world units.
+    pub(crate) fn local_affine(&self) -> Mat3 {
+        self.state.transform * transform::affine(Vec2::one(), 0., self.pos)
+    }
 }
 
 pub struct CircleBuilder<'a> {
@@ -68,6 +144,7 @@ impl<'a> CircleBuilder<'a> {
     pub fn with_color(self, color: Color) -> Self {
         let circle = self.graphics.circles.last_mut().unwrap();
         circle.style.fill_color = color;
+        circle.has_color = true;
         self
     }
 
@@ -80,6 +157,96 @@ impl<'a> CircleBuilder<'a> {
         circle.style.border_width = stroke;
         self
     }
+
+    /// Fill circle with a texture.
+    /// The texture is tinted with the color of the circle, if one is set before or after.
+    pub fn with_texture(self, texture: Handle<Texture>) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.texture = Some(texture);
+        self
+    }
+
+    /// Define how the texture is mapped onto the circle.
+    pub fn with_texture_fit(self, fit: TextureFit) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.uv.fit = fit;
+        self
+    }
+
+    /// Offset texture in texture coordinates.
+    pub fn with_texture_offset(self, x: f32, y: f32) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.uv.offset = Vec2::new(x, y);
+        self
+    }
+
+    /// Rotate texture around the center of the circle.
+    pub fn with_texture_rotation(self, rotation: f32) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.uv.rotation = rotation;
+        self
+    }
+
+    /// Add a shadow below the circle. The offset is in world units, so the shadow keeps its
+    /// direction and distance when the circle is rotated, scaled or attached to an entity.
+    /// The width of the soft edge is scaled with the circle.
+    pub fn with_shadow(self, offset: Vec2, blur: f32, color: Color) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.effects.shadow = Some(Shadow { offset, blur, color });
+        self
+    }
+
+    /// Add a glow fading out over `radius` world units around the circle, scaled with it.
+    pub fn with_glow(self, radius: f32, color: Color) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.effects.glow = Some(Glow { radius, color });
+        self
+    }
+}
+
+pub struct CircleOutlineBuilder<'a> {
+    pub(crate) graphics: &'a mut Immediate2DGraphics,
+}
+
+impl<'a> CircleOutlineBuilder<'a> {
+    /// Define radius of circle.
+    pub fn with_radius(self, radius: f32) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.radius = radius;
+        self
+    }
+
+    /// Define color of the outline.
+    pub fn with_color(self, color: Color) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.style.border_color = color;
+        self
+    }
+
+    /// Define width of the outline in world units.
+    pub fn with_stroke(self, stroke: f32) -> Self {
+        let circle = self.graphics.circles.last_mut().unwrap();
+        circle.outline = Some(stroke);
+        self
+    }
+}
+
+impl_lifetime!(CircleBuilder, circles);
+impl_lifetime!(CircleOutlineBuilder, circles);
+impl_shape_id!(CircleBuilder, circles);
+impl_shape_id!(CircleOutlineBuilder, circles);
+
+impl<'a> Deref for CircleOutlineBuilder<'a> {
+    type Target = Immediate2DGraphics;
+    fn deref(&self) -> &Self::Target {
+        &*self.graphics
+    }
+}
+
+impl<'a> DerefMut for CircleOutlineBuilder<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.graphics
+    }
 }
 
 impl<'a> Deref for CircleBuilder<'a> {
@@ -97,7 +264,7 @@ impl<'a> DerefMut for CircleBuilder<'a> {
 
 const CIRCLE_PIPELINE: Handle<PipelineDescriptor> = Handle::from_u128(324098732123374799960298734098274887483);
 
-const CIRCLE_STYLE_NODE: &str = "CircleStyle";
+pub(crate) const CIRCLE_STYLE_NODE: &str = "CircleStyle";
 
 pub fn add_render_graph(resources: &Resources) {
         let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
@@ -116,7 +283,7 @@ pub fn add_render_graph(resources: &Resources) {
         let fragment_shader_handle = shaders.add(
             Shader::from_glsl(ShaderStage::Fragment, include_str!("shader/circle.frag"))
         );
-        let pipeline_descriptor = PipelineDescriptor::default_config(ShaderStages {
+        let pipeline_descriptor = textures::shape_pipeline(ShaderStages {
             vertex: vertex_shader_handle,
             fragment: Some(fragment_shader_handle),
         });
@@ -125,17 +292,61 @@ pub fn add_render_graph(resources: &Resources) {
 
 }
 
+#[derive(Default, Bundle)]
+struct CircleComponents {
+    pub mesh: Handle<Mesh>,
+    pub draw: Draw,
+    pub render_pipelines: RenderPipelines,
+    pub main_pass: MainPass,
+    pub canvas_pass: CanvasPass,
+    pub target: ShapeTarget,
+    pub transform: Transform,
+}
+
+pub(crate) fn calc_transform_for_circle(circle: &Circle) -> Mat4 {
+    let size = Vec2::splat(circle.style.extent * 2.);
+    let local = transform::affine(size, 0., circle.pos);
+    transform::affine_to_mat4(&(circle.state.transform * local), circle.state.z)
+}
+
+impl PooledShape for Circle {
+    fn state(&self) -> &ShapeState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ShapeState {
+        &mut self.state
+    }
+
+    fn view_bounds(&self, _textures: &Assets<Texture>) -> Option<bounds::Bounds> {
+        let margin = self.effects.margin(&self.local_affine()) * transform::scale_factor(&self.state.transform);
+        Some(self.bounds().expanded(margin))
+    }
+
+    fn prepare(self, textures: &Assets<Texture>) -> Option<Self> {
+        Some(prepare_circle(self, textures))
+    }
+
+    fn quad_transform(&self) -> Mat4 {
+        calc_transform_for_circle(self)
+    }
+}
+
 pub fn circle_update_system(
     mut commands: Commands,
     mut immediate_graphics: ResMut<Immediate2DGraphics>,
-    mut query: Query<(
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    textures: Res<Assets<Texture>>,
+    mut query: Query<Without<RetainedShape, (
         &mut Draw, 
         &mut CircleStyle, 
-        &mut Translation,
-        &mut Scale,
-    )>,
+        &mut ShapeTexture,
+        &mut RenderPipelines,
+        &mut Transform,
+        &mut ShapeTarget,
+    )>>,
 ) {
-    commands.spawn(Camera2dComponents::default());
+    let start = Instant::now();
 
     let circle_render_pipelines = || // Workaround because RenderPipelines is not Clone.
         RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
@@ -157,37 +368,103 @@ pub fn circle_update_system(
         ]);
 
     let mut query_borrow = query.iter();
-    let mut query_iter = query_borrow.iter();
 
-    let mut circle_iter = immediate_graphics.circles.drain(..);
+    let graphics = &mut *immediate_graphics;
+    let pending = graphics.groups.take_pending(|shapes| &mut shapes.circles);
+    let mut pool = ShapePool {
+        resolver: &graphics.resolver,
+        groups: &graphics.groups,
+        textures: &textures,
+        stats: &mut stats,
+    };
+
+    pool.update(
+        &graphics.circles,
+        pending,
+        query_borrow.iter(),
+        |(mut draw, mut style, mut texture, mut pipelines, mut transform, mut target), circle| {
+            draw.is_visible = circle.is_some();
+            if let Some((circle, quad)) = circle {
+                textures::set_texture_shader_def(&mut pipelines, circle.texture.is_some());
+                texture.texture = circle.texture;
+                *transform = quad;
+                *target = ShapeTarget::of(&circle.state);
+                *style = circle.style;
+            }
+        },
+        |circle, is_visible, transform, retained| {
+            let entity = spawn_circle(&mut commands, circle, circle_render_pipelines(), is_visible, transform);
+            if let Some(retained) = retained {
+                entity.with(retained);
+            }
+        },
+    );
 
-    for (mut draw, mut style, mut trans, mut scale) in &mut query_iter {
-        let circle = if let Some(circle) = circle_iter.next() {
-            circle
-        } else {
-            draw.is_visible = false;
-            break;
-        };
+    stats.submitted.circles = graphics.circles.len();
 
-        draw.is_visible = true;
-        *style = circle.style;
-        *scale = Scale::from(circle.radius * 2.);
-        *trans = Translation::from(circle.pos.extend(0.));
+    stats.update_time += start.elapsed();
+}
+
+fn spawn_circle<'a>(
+    commands: &'a mut Commands,
+    circle: Circle,
+    mut render_pipelines: RenderPipelines,
+    is_visible: bool,
+    transform: Transform,
+) -> &'a mut Commands {
+    textures::set_texture_shader_def(&mut render_pipelines, circle.texture.is_some());
+
+    commands.spawn(CircleComponents {
+        mesh: QUAD_HANDLE,
+        draw: Draw {
+            is_visible,
+            is_transparent: true,
+            ..Default::default()
+        },
+        render_pipelines,
+        transform,
+        target: ShapeTarget::of(&circle.state),
+        ..Default::default()
+    })
+    .with(circle.style)
+    .with(ShapeTexture { texture: circle.texture })
+}
+
+/// Writes the geometry, uv transform, effects, clipping and fading of a circle into its style.
+pub(crate) fn prepare_circle(mut circle: Circle, textures: &Assets<Texture>) -> Circle {
+    circle.style.clip = bounds::clip_uniform(&circle.state.clip);
+
+    // the shader multiplies the texture with the fill color, white unless one was set.
+    if circle.texture.is_some() && !circle.has_color {
+        circle.style.fill_color = Color::WHITE;
     }
 
-    for (mut draw, _, _, _) in query_iter {
-        draw.is_visible = false;
+    let opacity = circle.state.opacity();
+    circle.style.fill_color = lifetime::fade(circle.style.fill_color, opacity);
+    circle.style.border_color = lifetime::fade(circle.style.border_color, opacity);
+
+    let affine = circle.local_affine();
+    let effects = circle.effects.uniforms(&affine);
+    circle.style.shadow_color = lifetime::fade(effects.shadow_color, opacity);
+    circle.style.glow_color = lifetime::fade(effects.glow_color, opacity);
+    circle.style.shadow = effects.shadow;
+    circle.style.glow_radius = effects.glow_radius;
+    circle.style.radius = circle.radius;
+    circle.style.extent = circle.radius + circle.effects.margin(&affine);
+
+    // the shader compares the squared distance to the center against the border width.
+    if let Some(stroke) = circle.outline {
+        let inner = f32::max(circle.radius - stroke, 0.) / circle.radius;
+        circle.style.border_width = 1. - inner * inner;
     }
 
-    for circle in circle_iter {
-        commands.spawn(MeshComponents {
-            mesh: QUAD_HANDLE,
-            render_pipelines: circle_render_pipelines(),
-            scale: Scale::from(circle.radius * 2.),
-            translation: Translation::from(circle.pos.extend(0.)),
-            ..Default::default()
-        })
-        .with(circle.style);
+    if let Some(texture) = &circle.texture {
+        let size = Vec2::new(circle.radius * 2., circle.radius * 2.);
+        let texture_size = textures.get(texture).map(|texture| texture.size);
+        let (uv_transform, uv_offset) = circle.uv.uniforms(size, texture_size);
+        circle.style.uv_transform = uv_transform;
+        circle.style.uv_offset = uv_offset;
     }
+    circle
 }
 
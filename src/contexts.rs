@@ -0,0 +1,125 @@
+use bevy::{
+    core::FloatOrd,
+    render::{
+        camera::{ActiveCameras, Camera, VisibleEntities, VisibleEntity},
+        render_graph::{base, CameraNode, PassNode, RenderGraph},
+    },
+    prelude::*,
+};
+use super::scope::ScopedGraphics;
+use super::state::ShapeState;
+use super::canvas::CanvasCamera;
+use base::MainPass;
+
+/// Binds a camera to a named graphics context. The camera only renders
+/// the shapes drawn with `Immediate2DGraphics::context` of that name.
+#[derive(Debug, Clone, Copy)]
+pub struct GraphicsContext {
+    pub name: &'static str,
+}
+
+/// Where a shape entity is rendered: the canvas it is drawn into and
+/// the context it belongs to, `None` for the main pass and the default context.
+#[derive(Debug, Default, Clone)]
+pub struct ShapeTarget {
+    pub canvas: Option<Handle<Texture>>,
+    pub context: Option<&'static str>,
+}
+
+impl ShapeTarget {
+    pub(crate) fn of(state: &ShapeState) -> Self {
+        Self {
+            canvas: state.canvas,
+            context: state.context,
+        }
+    }
+}
+
+/// Graphics whose shapes are drawn in a named context.
+/// Restores the previous context when dropped.
+pub type ContextGraphics<'a> = ScopedGraphics<'a, Option<&'static str>>;
+
+pub trait AddGraphicsContext {
+    /// Add a 2d camera rendering the shapes of the context `name` in the main pass,
+    /// after the default camera. Its `Translation` and `Scale` are independent of
+    /// the default camera, which makes it suitable for screen space overlays.
+    fn add_graphics_context(&mut self, name: &'static str) -> &mut Self;
+}
+
+impl AddGraphicsContext for AppBuilder {
+    fn add_graphics_context(&mut self, name: &'static str) -> &mut Self {
+        {
+            let resources = self.resources();
+            let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
+
+            render_graph.add_system_node(name, CameraNode::new(name));
+            render_graph.add_node_edge(name, base::node::MAIN_PASS).unwrap();
+
+            let main_pass: &mut PassNode<&MainPass> = render_graph.get_node_mut(base::node::MAIN_PASS).unwrap();
+            main_pass.add_camera(name);
+
+            let mut active_cameras = resources.get_mut::<ActiveCameras>().unwrap();
+            active_cameras.add(name);
+        }
+
+        let camera_entity = self.app.world.spawn(Camera2dComponents {
+            camera: Camera {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        self.app.world.insert_one(camera_entity, GraphicsContext { name }).unwrap();
+
+        self
+    }
+}
+
+/// Lists the shape entities every camera draws. Shapes are turned into entities after
+/// bevy collected the visible entities of the cameras in `stage::POST_UPDATE`, so the shapes
+/// collected there are replaced with the visible shapes of this frame, back to front.
+/// Canvas cameras only get the shapes drawn into their canvas and context cameras the
+/// shapes of their context. All other cameras get the shapes of the default context
+/// drawn into the main pass.
+pub fn shape_visibility_system(
+    mut cameras: Query<(Option<&CanvasCamera>, Option<&GraphicsContext>, &mut VisibleEntities)>,
+    mut shapes: Query<(Entity, &Draw, &Transform, &ShapeTarget)>,
+    draws: Query<&Draw>,
+) {
+    let mut visible_shapes: Vec<(Entity, f32, ShapeTarget)> = Vec::new();
+    for (entity, draw, transform, target) in &mut shapes.iter() {
+        if draw.is_visible {
+            visible_shapes.push((entity, transform.value.w_axis().z(), target.clone()));
+        }
+    }
+    visible_shapes.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    for (canvas_camera, context, mut visible_entities) in &mut cameras.iter() {
+        let canvas = canvas_camera.map(|camera| camera.texture);
+        let context = context.map(|context| context.name);
+
+        // opaque entities come first and transparent ones after, back to front.
+        // shapes are transparent and go after the other transparent entities.
+        let mut opaque = Vec::new();
+        let mut transparent = Vec::new();
+        for visible_entity in visible_entities.value.drain(..) {
+            if shapes.get::<ShapeTarget>(visible_entity.entity).is_ok() {
+                continue;
+            }
+            let is_transparent = draws.get::<Draw>(visible_entity.entity)
+                .map(|draw| draw.is_transparent)
+                .unwrap_or(false);
+            if is_transparent {
+                transparent.push(visible_entity);
+            } else {
+                opaque.push(visible_entity);
+            }
+        }
+
+        visible_entities.value.extend(opaque);
+        visible_entities.value.extend(transparent);
+        visible_entities.value.extend(visible_shapes.iter()
+            .filter(|(_, _, target)| target.canvas == canvas && target.context == context)
+            .map(|(entity, z, _)| VisibleEntity { entity: *entity, order: FloatOrd(-z) }));
+    }
+}
@@ -0,0 +1,90 @@
+use bevy::{math::Mat2, prelude::*};
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Shadow {
+    /// Offset of the shadow in world units, independent of the rotation and scale of the shape.
+    pub offset: Vec2,
+    /// Width of the soft edge of the shadow, scaled with the shape.
+    pub blur: f32,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Glow {
+    /// Distance the glow fades out over in world units, scaled with the shape.
+    pub radius: f32,
+    pub color: Color,
+}
+
+/// Shadow and glow around a shape, rendered from the distance to its edge.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Effects {
+    pub shadow: Option<Shadow>,
+    pub glow: Option<Glow>,
+}
+
+/// Uniforms shared by the styles of shapes supporting effects.
+pub(crate) struct EffectUniforms {
+    pub shadow_color: Color,
+    pub glow_color: Color,
+    /// `(offset.x, offset.y, blur, 0)` with the offset in the local space of the shape.
+    pub shadow: Vec4,
+    pub glow_radius: f32,
+}
+
+impl Effects {
+    /// Shadow offset in the local space of a shape, whose `affine` maps it to world units
+    /// without its size. Zero for shapes scaled to nothing.
+    pub(crate) fn shadow_offset(&self, affine: &Mat3) -> Vec2 {
+        let offset = self.shadow.map_or(Vec2::zero(), |shadow| shadow.offset);
+        let linear = Mat2::from_cols(affine.x_axis().truncate(), affine.y_axis().truncate());
+        let determinant = linear.determinant();
+        if determinant == 0. || !determinant.is_finite() {
+            return Vec2::zero();
+        }
+        linear.inverse() * offset
+    }
+
+    /// Distance the quad of a shape has to be extended by in its local space,
+    /// so shadow and glow fit in. `affine` is the one of `shadow_offset`.
+    pub(crate) fn margin(&self, affine: &Mat3) -> f32 {
+        let offset = self.shadow_offset(affine);
+        let shadow = self.shadow
+            .map(|shadow| f32::max(offset.x().abs(), offset.y().abs()) + shadow.blur)
+            .unwrap_or(0.);
+        let glow = self.glow
+            .map(|glow| glow.radius)
+            .unwrap_or(0.);
+
+        f32::max(shadow, glow)
+    }
+
+    /// The shaders work in the local space of the shape with y pointing down,
+    /// so the shadow offset is transformed back and flipped. `affine` is the one of `shadow_offset`.
+    pub(crate) fn uniforms(&self, affine: &Mat3) -> EffectUniforms {
+        let transparent = Color::rgba(0., 0., 0., 0.);
+
+        let (shadow_color, shadow) = if let Some(shadow) = self.shadow {
+            let offset = self.shadow_offset(affine);
+            (shadow.color, Vec4::new(offset.x(), -offset.y(), shadow.blur, 0.))
+        } else {
+            (transparent, Vec4::zero())
+        };
+
+        let (glow_color, glow_radius) = if let Some(glow) = self.glow {
+            (glow.color, glow.radius)
+        } else {
+            (transparent, 0.)
+        };
+
+        EffectUniforms {
+            shadow_color,
+            glow_color,
+            shadow,
+            glow_radius,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use super::Immediate2DGraphics;
+use super::stats::Immediate2DGraphicsStats;
+use super::transform;
+
+/// Handle to a group of shapes submitted once with `Immediate2DGraphics::retain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeGroupHandle(u32);
+
+/// Marks the entity of a retained shape, so it is not reused for immediate shapes.
+#[derive(Debug)]
+pub(crate) struct RetainedShape {
+    pub group: ShapeGroupHandle,
+    /// Transformation of the shape, before the one of its group.
+    pub base: Mat4,
+}
+
+#[derive(Debug)]
+struct GroupState {
+    visible: bool,
+    transform: Mat3,
+    /// Whether the entities of the group have to be updated.
+    changed: bool,
+}
+
+/// Shared by all graphics, so groups created in buffers of a `GraphicsQueue` get unique handles.
+static NEXT_GROUP_ID: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Default)]
+pub(crate) struct Groups {
+    /// Groups whose shapes have not been spawned yet.
+    pending: Vec<(ShapeGroupHandle, Immediate2DGraphics)>,
+    states: HashMap<ShapeGroupHandle, GroupState>,
+    /// Groups whose entities have to be despawned.
+    removed: Vec<ShapeGroupHandle>,
+}
+
+impl Groups {
+    pub(crate) fn add(&mut self, shapes: Immediate2DGraphics) -> ShapeGroupHandle {
+        let handle = ShapeGroupHandle(NEXT_GROUP_ID.fetch_add(1, Ordering::Relaxed));
+
+        self.pending.push((handle, shapes));
+        self.states.insert(handle, GroupState {
+            visible: true,
+            transform: Mat3::identity(),
+            changed: false,
+        });
+        handle
+    }
+
+    pub(crate) fn set_visible(&mut self, handle: ShapeGroupHandle, visible: bool) {
+        if let Some(state) = self.states.get_mut(&handle) {
+            state.visible = visible;
+            state.changed = true;
+        }
+    }
+
+    pub(crate) fn set_transform(&mut self, handle: ShapeGroupHandle, transform: Mat3) {
+        if let Some(state) = self.states.get_mut(&handle) {
+            state.transform = transform;
+            state.changed = true;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, handle: ShapeGroupHandle) {
+        if self.states.remove(&handle).is_some() {
+            self.pending.retain(|(pending, _)| *pending != handle);
+            self.removed.push(handle);
+        }
+    }
+
+    /// Moves the groups created in another graphics into these.
+    pub(crate) fn append(&mut self, other: &mut Groups) {
+        self.pending.append(&mut other.pending);
+        self.states.extend(other.states.drain());
+        self.removed.append(&mut other.removed);
+    }
+
+    /// Groups waiting to be spawned, e.g. to resolve the entities they are attached to.
+    pub(crate) fn pending(&self) -> impl Iterator<Item = &Immediate2DGraphics> {
+        self.pending.iter().map(|(_, shapes)| shapes)
+    }
+
+    /// Takes the shapes of one kind out of every group waiting to be spawned.
+    pub(crate) fn take_pending<T>(
+        &mut self,
+        shapes: fn(&mut Immediate2DGraphics) -> &mut Vec<T>,
+    ) -> Vec<(ShapeGroupHandle, T)> {
+        self.pending.iter_mut()
+            .flat_map(|(handle, group)| {
+                let handle = *handle;
+                shapes(group).drain(..).map(move |shape| (handle, shape))
+            })
+            .collect()
+    }
+
+    /// Forgets groups whose shapes have all been spawned.
+    pub(crate) fn clear_spawned(&mut self) {
+        self.pending.retain(|(_, group)| group.states().next().is_some());
+    }
+
+    pub(crate) fn is_visible(&self, handle: ShapeGroupHandle) -> bool {
+        self.states.get(&handle).map(|state| state.visible).unwrap_or(false)
+    }
+
+    /// Transformation of a retained shape, including the one of its group.
+    pub(crate) fn transform(&self, retained: &RetainedShape) -> Transform {
+        let group_transform = self.states.get(&retained.group)
+            .map(|state| state.transform)
+            .unwrap_or_else(Mat3::identity);
+        Transform::new(transform::affine_to_mat4(&group_transform, 0.) * retained.base)
+    }
+}
+
+/// Applies changes of visibility and transformation to the entities of retained groups
+/// and despawns the entities of removed groups.
+pub fn group_system(
+    mut commands: Commands,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    mut query: Query<(Entity, &RetainedShape, &mut Draw, &mut Transform)>,
+) {
+    let groups = &mut immediate_graphics.groups;
+
+    for (entity, retained, mut draw, mut transform) in &mut query.iter() {
+        if groups.removed.contains(&retained.group) {
+            commands.despawn(entity);
+            continue;
+        }
+
+        let changed = groups.states.get(&retained.group)
+            .map(|state| state.changed)
+            .unwrap_or(false);
+        if changed {
+            draw.is_visible = groups.is_visible(retained.group);
+            *transform = groups.transform(retained);
+        }
+        if draw.is_visible {
+            stats.add_quads(1);
+        }
+    }
+
+    groups.removed.clear();
+    for state in groups.states.values_mut() {
+        state.changed = false;
+    }
+}
@@ -0,0 +1,351 @@
+use bevy::{
+    render::{
+        pipeline::{
+            PipelineSpecialization, PipelineDescriptor, RenderPipeline,
+            DynamicBinding
+        },
+        shader::{ShaderStage, ShaderStages},
+        renderer::{RenderResource, RenderResources},
+        render_graph::{RenderGraph, base, RenderResourcesNode}
+    },
+    core::Byteable,
+    ecs::Without,
+    sprite::QUAD_HANDLE,
+    prelude::*,
+};
+use std::ops::{DerefMut, Deref};
+use std::time::Instant;
+use super::Immediate2DGraphics;
+use super::stats::Immediate2DGraphicsStats;
+use super::state::ShapeState;
+use super::textures::{self, ShapeTexture};
+use super::transform;
+use super::bounds;
+use super::lifetime;
+use super::canvas::CanvasPass;
+use super::contexts::ShapeTarget;
+use super::groups::RetainedShape;
+use super::pool::{PooledShape, ShapePool};
+use base::MainPass;
+
+#[derive(Debug, Clone, RenderResources, RenderResource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[render_resources(from_self)]
+#[allow(unused)]
+#[repr(C)]
+pub struct ImageStyle {
+    pub tint: Color,
+    /// Corners of the drawn part of the texture, `(min.x, min.y, max.x, max.y)` in uvs.
+    pub uv_rect: Vec4,
+    pub clip: Vec4,
+}
+unsafe impl Byteable for ImageStyle {}
+
+impl Default for ImageStyle {
+    fn default() -> Self {
+        Self {
+            tint: Color::WHITE,
+            uv_rect: Vec4::new(0., 0., 1., 1.),
+            clip: bounds::clip_uniform(&None),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Image {
+    pub state: ShapeState,
+    pub style: ImageStyle,
+    pub texture: Handle<Texture>,
+    pub pos: Vec2,
+    /// Size in world units. Defaults to the size of the source rectangle in pixels.
+    pub size: Option<Vec2>,
+    pub rotation: f32,
+    /// Position and size of the drawn part of the texture in pixels.
+    pub source: Option<(Vec2, Vec2)>,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl Image {
+    pub fn new(state: ShapeState, texture: Handle<Texture>, x: f32, y: f32) -> Self {
+        Self {
+            state,
+            style: ImageStyle::default(),
+            texture,
+            pos: Vec2::new(x, y),
+            size: None,
+            rotation: 0.,
+            source: None,
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+
+    /// Bounds of the rotated image in the space it is transformed into, usually world units,
+    /// limited to its clip rectangle. `None` if no size was set, as it depends on the texture.
+    pub fn bounds(&self) -> Option<bounds::Bounds> {
+        let size = self.size?;
+        let local = transform::affine(Vec2::one(), self.rotation, self.pos);
+        let image = bounds::Bounds::from_center_size(Vec2::zero(), size.abs());
+        Some(self.state.clipped(image.transformed(&(self.state.transform * local))))
+    }
+}
+
+pub struct ImageBuilder<'a> {
+    pub(crate) graphics: &'a mut Immediate2DGraphics,
+}
+
+impl<'a> ImageBuilder<'a> {
+    /// Define size of image in world units.
+    pub fn with_size(self, width: f32, height: f32) -> Self {
+        let image = self.graphics.images.last_mut().unwrap();
+        image.size = Some(Vec2::new(width, height));
+        self
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Self {
+        let image = self.graphics.images.last_mut().unwrap();
+        image.rotation = rotation;
+        self
+    }
+
+    /// Multiply the texture with a color.
+    pub fn with_tint(self, color: Color) -> Self {
+        let image = self.graphics.images.last_mut().unwrap();
+        image.style.tint = color;
+        self
+    }
+
+    /// Only draw part of the texture, e.g. a cell of a sprite sheet.
+    /// Coordinates are in pixels, starting at the top left corner of the texture.
+    pub fn with_source_rect(self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        let image = self.graphics.images.last_mut().unwrap();
+        image.source = Some((Vec2::new(x, y), Vec2::new(width, height)));
+        self
+    }
+
+    /// Mirror the image horizontally and/or vertically.
+    pub fn with_flip(self, flip_x: bool, flip_y: bool) -> Self {
+        let image = self.graphics.images.last_mut().unwrap();
+        image.flip_x = flip_x;
+        image.flip_y = flip_y;
+        self
+    }
+}
+
+impl_lifetime!(ImageBuilder, images);
+impl_shape_id!(ImageBuilder, images);
+
+impl<'a> Deref for ImageBuilder<'a> {
+    type Target = Immediate2DGraphics;
+    fn deref(&self) -> &Self::Target {
+        &*self.graphics
+    }
+}
+
+impl<'a> DerefMut for ImageBuilder<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.graphics
+    }
+}
+
+const IMAGE_PIPELINE: Handle<PipelineDescriptor> = Handle::from_u128(154820967332548107745920386719046287291);
+
+pub(crate) const IMAGE_STYLE_NODE: &str = "ImageStyle";
+
+pub fn add_render_graph(resources: &Resources) {
+        let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
+
+        let mut shaders = resources.get_mut::<Assets<Shader>>().unwrap();
+
+        let mut pipelines = resources.get_mut::<Assets<PipelineDescriptor>>().unwrap();
+
+        let resource_node = RenderResourcesNode::<ImageStyle>::new(true);
+        render_graph.add_system_node(IMAGE_STYLE_NODE, resource_node);
+        render_graph.add_node_edge(IMAGE_STYLE_NODE, base::node::MAIN_PASS).unwrap();
+
+        let vertex_shader_handle = shaders.add(
+            Shader::from_glsl(ShaderStage::Vertex, include_str!("shader/image.vert"))
+        );
+        let fragment_shader_handle = shaders.add(
+            Shader::from_glsl(ShaderStage::Fragment, include_str!("shader/image.frag"))
+        );
+        let pipeline_descriptor = textures::shape_pipeline(ShaderStages {
+            vertex: vertex_shader_handle,
+            fragment: Some(fragment_shader_handle),
+        });
+
+        pipelines.set(IMAGE_PIPELINE, pipeline_descriptor);
+
+}
+
+#[derive(Default, Bundle)]
+struct ImageComponents {
+    pub mesh: Handle<Mesh>,
+    pub draw: Draw,
+    pub render_pipelines: RenderPipelines,
+    pub main_pass: MainPass,
+    pub canvas_pass: CanvasPass,
+    pub target: ShapeTarget,
+    pub transform: Transform,
+}
+
+pub(crate) fn calc_transform_for_image(image: &Image) -> Mat4 {
+    let local = transform::affine(image.size.unwrap(), image.rotation, image.pos);
+    transform::affine_to_mat4(&(image.state.transform * local), image.state.z)
+}
+
+/// Size of the texture of an image in pixels, zero while it is loading.
+fn texture_size(image: &Image, textures: &Assets<Texture>) -> Vec2 {
+    textures.get(&image.texture)
+        .map(|texture| texture.size)
+        .unwrap_or_else(Vec2::zero)
+}
+
+/// Resolves size and uvs of an image, once its texture is known, and writes its clipping and fading.
+pub(crate) fn prepare_image(mut image: Image, textures: &Assets<Texture>) -> Image {
+    image.style.clip = bounds::clip_uniform(&image.state.clip);
+    image.style.tint = lifetime::fade(image.style.tint, image.state.opacity());
+
+    let texture_size = texture_size(&image, textures);
+
+    let (source_pos, source_size) = image.source.unwrap_or((Vec2::zero(), texture_size));
+
+    if image.size.is_none() {
+        image.size = Some(source_size);
+    }
+
+    if texture_size.x() > 0. && texture_size.y() > 0. {
+        let min = source_pos / texture_size;
+        let max = (source_pos + source_size) / texture_size;
+
+        let (min_x, max_x) = if image.flip_x { (max.x(), min.x()) } else { (min.x(), max.x()) };
+        let (min_y, max_y) = if image.flip_y { (max.y(), min.y()) } else { (min.y(), max.y()) };
+
+        image.style.uv_rect = Vec4::new(min_x, min_y, max_x, max_y);
+    }
+
+    image
+}
+
+impl PooledShape for Image {
+    fn state(&self) -> &ShapeState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ShapeState {
+        &mut self.state
+    }
+
+    fn view_bounds(&self, textures: &Assets<Texture>) -> Option<bounds::Bounds> {
+        if self.size.is_some() {
+            return self.bounds();
+        }
+        // without a size, an image is drawn with the size of its source, which can depend on its texture.
+        let source_size = self.source.map_or_else(|| texture_size(self, textures), |(_, size)| size);
+        Image { size: Some(source_size), ..self.clone() }.bounds()
+    }
+
+    fn prepare(self, textures: &Assets<Texture>) -> Option<Self> {
+        Some(prepare_image(self, textures))
+    }
+
+    fn quad_transform(&self) -> Mat4 {
+        calc_transform_for_image(self)
+    }
+}
+
+pub fn image_update_system(
+    mut commands: Commands,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    textures: Res<Assets<Texture>>,
+    mut query: Query<Without<RetainedShape, (
+        &mut Draw,
+        &mut ImageStyle,
+        &mut ShapeTexture,
+        &mut Transform,
+        &mut ShapeTarget,
+    )>>,
+) {
+    let start = Instant::now();
+
+    let image_render_pipelines = || // Workaround because RenderPipelines is not Clone.
+        RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
+            IMAGE_PIPELINE,
+            PipelineSpecialization {
+                dynamic_bindings: vec![
+                    DynamicBinding {
+                        bind_group: 1,
+                        binding: 0,
+                    },
+                    DynamicBinding {
+                        bind_group: 1,
+                        binding: 1,
+                    },
+                ],
+                ..Default::default()
+            },
+            )
+        ]);
+
+    let mut query_borrow = query.iter();
+
+    let graphics = &mut *immediate_graphics;
+    let pending = graphics.groups.take_pending(|shapes| &mut shapes.images);
+    let mut pool = ShapePool {
+        resolver: &graphics.resolver,
+        groups: &graphics.groups,
+        textures: &textures,
+        stats: &mut stats,
+    };
+
+    pool.update(
+        &graphics.images,
+        pending,
+        query_borrow.iter(),
+        |(mut draw, mut style, mut texture, mut transform, mut target), image| {
+            draw.is_visible = image.is_some();
+            if let Some((image, quad)) = image {
+                *transform = quad;
+                texture.texture = Some(image.texture);
+                *target = ShapeTarget::of(&image.state);
+                *style = image.style;
+            }
+        },
+        |image, is_visible, transform, retained| {
+            let entity = spawn_image(&mut commands, image, image_render_pipelines(), is_visible, transform);
+            if let Some(retained) = retained {
+                entity.with(retained);
+            }
+        },
+    );
+
+    stats.submitted.images = graphics.images.len();
+
+    stats.update_time += start.elapsed();
+}
+
+fn spawn_image<'a>(
+    commands: &'a mut Commands,
+    image: Image,
+    render_pipelines: RenderPipelines,
+    is_visible: bool,
+    transform: Transform,
+) -> &'a mut Commands {
+    commands.spawn(ImageComponents {
+        mesh: QUAD_HANDLE,
+        draw: Draw {
+            is_visible,
+            is_transparent: true,
+            ..Default::default()
+        },
+        render_pipelines,
+        transform,
+        target: ShapeTarget::of(&image.state),
+        ..Default::default()
+    })
+    .with(image.style)
+    .with(ShapeTexture { texture: Some(image.texture) })
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy::window::CursorMoved;
+use std::collections::HashMap;
+use super::Immediate2DGraphics;
+use super::picking::ShapeId;
+use super::transform;
+
+/// Sent every frame the cursor is over an interactive shape, for the topmost one.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeHovered {
+    pub id: ShapeId,
+    /// Cursor position in world units, as seen by the camera of the shape.
+    pub position: Vec2,
+}
+
+/// Sent when a mouse button is pressed over an interactive shape, for the topmost one.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeClicked {
+    pub id: ShapeId,
+    pub button: MouseButton,
+    /// Cursor position in world units, as seen by the camera of the shape.
+    pub position: Vec2,
+}
+
+/// Sent when the cursor moves while a mouse button pressed over an interactive shape is held.
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeDragged {
+    pub id: ShapeId,
+    pub button: MouseButton,
+    /// Cursor position in world units, as seen by the camera of the shape.
+    pub position: Vec2,
+    /// Movement of the cursor since the last event of the drag, in world units.
+    pub delta: Vec2,
+}
+
+/// Shape being dragged with a mouse button.
+#[derive(Debug)]
+struct Drag {
+    id: ShapeId,
+    button: MouseButton,
+    /// Context of the shape, whose camera the cursor is converted with while dragging.
+    context: Option<&'static str>,
+    position: Vec2,
+}
+
+/// Cursor and drags tracked by `interaction_system` across frames.
+#[derive(Default)]
+pub struct InteractionState {
+    cursor_moved: EventReader<CursorMoved>,
+    /// Cursor position in the primary window, in logical pixels from the bottom left corner.
+    cursor: Option<Vec2>,
+    drags: Vec<Drag>,
+}
+
+const BUTTONS: [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+/// Sends interaction events for the interactive shapes drawn in the last frame.
+/// The cursor is transformed into world space once for the camera of every graphics context,
+/// and shapes are tested with the cursor of their context. Shapes drawn into a canvas
+/// are not picked with the cursor, because it is not known where the canvas is shown.
+pub fn interaction_system(
+    mut state: Local<InteractionState>,
+    immediate_graphics: Res<Immediate2DGraphics>,
+    windows: Res<Windows>,
+    cursor_moved: Res<Events<CursorMoved>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut hovered: ResMut<Events<ShapeHovered>>,
+    mut clicked: ResMut<Events<ShapeClicked>>,
+    mut dragged: ResMut<Events<ShapeDragged>>,
+) {
+    let state = &mut *state;
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    for event in state.cursor_moved.iter(&cursor_moved) {
+        if event.id == window.id {
+            state.cursor = Some(event.position);
+        }
+    }
+
+    let cursor = match state.cursor {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    let window_size = Vec2::new(window.width as f32, window.height as f32);
+    let positions: HashMap<Option<&'static str>, Vec2> = immediate_graphics.resolver.window_viewports()
+        .map(|(context, viewport)| {
+            let local = viewport.bounds.min + cursor / window_size * viewport.bounds.size();
+            (context, transform::transform_point(&viewport.transform, local))
+        })
+        .collect();
+
+    let hovered_shape = immediate_graphics.picking
+        .interactive_at(|target| if target.canvas.is_none() { positions.get(&target.context).copied() } else { None })
+        .map(|(shape, position)| (shape.id, shape.target.context, position));
+    if let Some((id, _, position)) = hovered_shape {
+        hovered.send(ShapeHovered { id, position });
+    }
+
+    state.drags.retain(|drag| mouse_buttons.pressed(drag.button));
+
+    for drag in state.drags.iter_mut() {
+        let position = match positions.get(&drag.context) {
+            Some(position) => *position,
+            None => continue,
+        };
+        if drag.position != position {
+            dragged.send(ShapeDragged {
+                id: drag.id,
+                button: drag.button,
+                position,
+                delta: position - drag.position,
+            });
+            drag.position = position;
+        }
+    }
+
+    if let Some((id, context, position)) = hovered_shape {
+        for button in BUTTONS.iter().copied() {
+            if mouse_buttons.just_pressed(button) {
+                clicked.send(ShapeClicked { id, button, position });
+                state.drags.push(Drag { id, button, context, position });
+            }
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use bevy::{
+    render::{
+        draw::{DrawContext, Drawable},
+        renderer::{RenderResourceBindings, AssetRenderResourceBindings},
+        render_graph::base,
+    },
+    text::{DrawableText, FontAtlasSet, TextStyle},
+    sprite::TextureAtlas,
+    prelude::*,
+};
+use ab_glyph::{PxScale, ScaleFont};
+use std::ops::{DerefMut, Deref};
+use std::time::Instant;
+use super::Immediate2DGraphics;
+use super::stats::Immediate2DGraphicsStats;
+use super::state::ShapeState;
+use super::transform;
+use super::lifetime;
+use super::canvas::CanvasPass;
+use super::contexts::ShapeTarget;
+use base::MainPass;
+
+/// Horizontal alignment of text relative to its position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HorizontalAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Vertical alignment of text relative to its position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VerticalAlign {
+    /// The top of the first line is at the position.
+    Top,
+    /// The baseline of the first line is at the position.
+    Baseline,
+    /// The bottom of the last line is at the position.
+    Bottom,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Label {
+    pub state: ShapeState,
+    pub value: String,
+    pub pos: Vec2,
+    /// Font of the text. Falls back to the default font of the graphics.
+    pub font: Option<Handle<Font>>,
+    pub size: f32,
+    pub color: Color,
+    pub horizontal_align: HorizontalAlign,
+    pub vertical_align: VerticalAlign,
+    /// Width after which lines are wrapped at word boundaries.
+    pub max_width: Option<f32>,
+}
+
+impl Label {
+    pub fn new(state: ShapeState, value: String, x: f32, y: f32) -> Self {
+        Self {
+            state,
+            value,
+            pos: Vec2::new(x, y),
+            font: None,
+            size: 20.,
+            color: Color::BLACK,
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: VerticalAlign::Baseline,
+            max_width: None,
+        }
+    }
+}
+
+pub struct LabelBuilder<'a> {
+    pub(crate) graphics: &'a mut Immediate2DGraphics,
+}
+
+impl<'a> LabelBuilder<'a> {
+    pub fn with_font(self, font: Handle<Font>) -> Self {
+        let label = self.graphics.labels.last_mut().unwrap();
+        label.font = Some(font);
+        self
+    }
+
+    /// Define font size in world units.
+    pub fn with_size(self, size: f32) -> Self {
+        let label = self.graphics.labels.last_mut().unwrap();
+        label.size = size;
+        self
+    }
+
+    pub fn with_color(self, color: Color) -> Self {
+        let label = self.graphics.labels.last_mut().unwrap();
+        label.color = color;
+        self
+    }
+
+    pub fn with_align(self, horizontal: HorizontalAlign, vertical: VerticalAlign) -> Self {
+        let label = self.graphics.labels.last_mut().unwrap();
+        label.horizontal_align = horizontal;
+        label.vertical_align = vertical;
+        self
+    }
+
+    /// Wrap lines that are wider than `max_width`.
+    pub fn with_max_width(self, max_width: f32) -> Self {
+        let label = self.graphics.labels.last_mut().unwrap();
+        label.max_width = Some(max_width);
+        self
+    }
+}
+
+impl_lifetime!(LabelBuilder, labels);
+
+impl<'a> Deref for LabelBuilder<'a> {
+    type Target = Immediate2DGraphics;
+    fn deref(&self) -> &Self::Target {
+        &*self.graphics
+    }
+}
+
+impl<'a> DerefMut for LabelBuilder<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.graphics
+    }
+}
+
+/// Laid out text of a label, drawn by `label_draw_system`.
+pub struct LabelLayout {
+    pub font: Handle<Font>,
+    pub style: TextStyle,
+    /// Bottom left corner and text of every line.
+    pub lines: Vec<(Vec3, String)>,
+}
+
+impl Default for LabelLayout {
+    fn default() -> Self {
+        Self {
+            font: Handle::default(),
+            style: TextStyle {
+                font_size: 0.,
+                color: Color::BLACK,
+            },
+            lines: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default, Bundle)]
+struct LabelComponents {
+    pub draw: Draw,
+    pub main_pass: MainPass,
+    pub canvas_pass: CanvasPass,
+    pub target: ShapeTarget,
+    pub transform: Transform,
+    pub layout: LabelLayout,
+}
+
+fn measure(font: &Font, size: f32, text: &str) -> f32 {
+    let scaled_font = ab_glyph::Font::as_scaled(&font.font, PxScale::from(size));
+
+    let mut width = 0.;
+    let mut last_glyph = None;
+
+    for character in text.chars() {
+        if character.is_control() {
+            continue;
+        }
+
+        let glyph = scaled_font.glyph_id(character);
+        if let Some(last_glyph) = last_glyph {
+            width += scaled_font.kern(last_glyph, glyph);
+        }
+        width += scaled_font.h_advance(glyph);
+        last_glyph = Some(glyph);
+    }
+
+    width
+}
+
+/// Splits text into lines at newlines and, if `max_width` is given, at word boundaries.
+fn wrap(font: &Font, size: f32, text: &str, max_width: Option<f32>) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let max_width = if let Some(max_width) = max_width {
+            max_width
+        } else {
+            lines.push(paragraph.to_string());
+            continue;
+        };
+
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if line.is_empty() {
+                line.push_str(word);
+                continue;
+            }
+
+            let candidate = format!("{} {}", line, word);
+            if measure(font, size, &candidate) > max_width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Applies the transformation of the transform stack to the position, size and
+/// wrapping width and fades the color. Text is always drawn upright.
+fn transform_label(mut label: Label) -> Label {
+    label.color = lifetime::fade(label.color, label.state.opacity());
+    let affine = label.state.transform;
+    let scale = transform::scale_factor(&affine);
+    label.pos = transform::transform_point(&affine, label.pos);
+    label.size *= scale;
+    // scaled like the size, so the text wraps at the same words.
+    label.max_width = label.max_width.map(|max_width| max_width * scale);
+    label
+}
+
+fn layout_label(label: Label, font_handle: Handle<Font>, font: &Font) -> LabelLayout {
+    let scaled_font = ab_glyph::Font::as_scaled(&font.font, PxScale::from(label.size));
+    let line_height = scaled_font.height() + scaled_font.line_gap();
+
+    let lines = wrap(font, label.size, &label.value, label.max_width);
+
+    // bottom of the first line, the descent is negative.
+    let first_bottom = match label.vertical_align {
+        VerticalAlign::Top => label.pos.y() - scaled_font.height(),
+        VerticalAlign::Baseline => label.pos.y() + scaled_font.descent(),
+        VerticalAlign::Bottom => label.pos.y() + (lines.len().max(1) - 1) as f32 * line_height,
+    };
+
+    let lines = lines.into_iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let width = measure(font, label.size, &line);
+            let x = match label.horizontal_align {
+                HorizontalAlign::Left => label.pos.x(),
+                HorizontalAlign::Center => label.pos.x() - width / 2.,
+                HorizontalAlign::Right => label.pos.x() - width,
+            };
+            let y = first_bottom - i as f32 * line_height;
+            (Vec3::new(x, y, label.state.z), line)
+        })
+        .collect();
+
+    LabelLayout {
+        font: font_handle,
+        style: TextStyle {
+            font_size: label.size,
+            color: label.color,
+        },
+        lines,
+    }
+}
+
+pub fn label_update_system(
+    mut commands: Commands,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    fonts: Res<Assets<Font>>,
+    mut font_atlas_sets: ResMut<Assets<FontAtlasSet>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut query: Query<(
+        &mut Draw,
+        &mut LabelLayout,
+        &mut Transform,
+        &mut ShapeTarget,
+    )>,
+) {
+    let start = Instant::now();
+
+    let graphics = &mut *immediate_graphics;
+    let default_font = graphics.default_font;
+    let resolver = &graphics.resolver;
+
+    // labels whose font is not loaded yet are skipped.
+    let layouts: Vec<(ShapeTarget, LabelLayout)> = graphics.labels.iter().cloned()
+        .filter_map(|mut label| {
+            if !resolver.resolve(&mut label.state) {
+                return None;
+            }
+            let font_handle = label.font.or(default_font)?;
+            let font = fonts.get(&font_handle)?;
+            let target = ShapeTarget::of(&label.state);
+            Some((target, layout_label(transform_label(label), font_handle, font)))
+        })
+        .collect();
+    stats.submitted.labels = graphics.labels.len();
+
+    for (_, layout) in layouts.iter() {
+        let font_atlas_set = font_atlas_sets.get_or_insert_with(
+            Handle::from_id(layout.font.id),
+            || FontAtlasSet::new(layout.font),
+        );
+
+        for (_, line) in layout.lines.iter() {
+            font_atlas_set.add_glyphs_to_atlas(
+                &fonts,
+                &mut texture_atlases,
+                &mut textures,
+                layout.style.font_size,
+                line,
+            );
+        }
+    }
+
+    let mut query_borrow = query.iter();
+    let mut query_iter = query_borrow.iter();
+
+    let mut layout_iter = layouts.into_iter();
+
+    for (mut draw, mut label_layout, mut transform, mut target) in &mut query_iter {
+        let (shape_target, layout) = if let Some(layout) = layout_iter.next() {
+            layout
+        } else {
+            draw.is_visible = false;
+            stats.hidden += 1;
+            break;
+        };
+
+        let z = layout.lines.first().map(|(pos, _)| pos.z()).unwrap_or(0.);
+
+        draw.is_visible = true;
+        stats.pooled += 1;
+        *transform = Transform::new(Mat4::from_translation(Vec3::new(0., 0., z)));
+        *target = shape_target;
+        *label_layout = layout;
+    }
+
+    for (mut draw, _, _, _) in query_iter {
+        draw.is_visible = false;
+        stats.hidden += 1;
+    }
+
+    for (target, layout) in layout_iter {
+        stats.spawned += 1;
+        let z = layout.lines.first().map(|(pos, _)| pos.z()).unwrap_or(0.);
+
+        commands.spawn(LabelComponents {
+            draw: Draw {
+                is_transparent: true,
+                ..Default::default()
+            },
+            transform: Transform::new(Mat4::from_translation(Vec3::new(0., 0., z))),
+            target,
+            layout,
+            ..Default::default()
+        });
+    }
+
+    stats.update_time += start.elapsed();
+}
+
+/// Draws the glyphs of every visible label, like the text widget of bevy_ui.
+pub fn label_draw_system(
+    mut draw_context: DrawContext,
+    fonts: Res<Assets<Font>>,
+    font_atlas_sets: Res<Assets<FontAtlasSet>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut render_resource_bindings: ResMut<RenderResourceBindings>,
+    mut asset_render_resource_bindings: ResMut<AssetRenderResourceBindings>,
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    mut query: Query<(&mut Draw, &LabelLayout)>,
+) {
+    for (mut draw, layout) in &mut query.iter() {
+        if !draw.is_visible {
+            continue;
+        }
+
+        let font = if let Some(font) = fonts.get(&layout.font) {
+            font
+        } else {
+            continue;
+        };
+
+        let font_atlas_set = if let Some(font_atlas_set) = font_atlas_sets.get(&Handle::from_id(layout.font.id)) {
+            font_atlas_set
+        } else {
+            continue;
+        };
+
+        for (position, line) in layout.lines.iter() {
+            let mut drawable_text = DrawableText {
+                font,
+                font_atlas_set,
+                texture_atlases: &texture_atlases,
+                render_resource_bindings: &mut render_resource_bindings,
+                asset_render_resource_bindings: &mut asset_render_resource_bindings,
+                position: *position,
+                container_size: Vec2::new(f32::MAX, layout.style.font_size),
+                style: &layout.style,
+                text: line,
+            };
+            // a line that can't be drawn this frame, e.g. while the render resources of
+            // its font atlas are missing, is skipped instead of stopping the app.
+            if drawable_text.draw(&mut draw, &mut draw_context).is_err() {
+                continue;
+            }
+            stats.add_quads(line.chars().filter(|c| !c.is_whitespace()).count());
+        }
+    }
+}
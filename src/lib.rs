@@ -1,23 +1,109 @@
 use bevy::prelude::*;
 
-use circles::{CircleBuilder, Circle};
-use rectangles::{RectangleBuilder, Rectangle};
-use lines::{LineBuilder, Line};
+use circles::{CircleBuilder, CircleOutlineBuilder};
+use rectangles::{RectangleBuilder, RectangleOutlineBuilder};
+use lines::LineBuilder;
+use images::ImageBuilder;
+use labels::{LabelBuilder, Label};
+use state::{ShapeState, DEPTH_STEP, LAYER_DEPTH, MAX_LAYER};
+use resolve::Resolver;
+use groups::Groups;
+use picking::Picking;
 
+pub use circles::Circle;
+pub use rectangles::Rectangle;
+pub use lines::Line;
+pub use images::Image;
+pub use textures::TextureFit;
+pub use labels::{HorizontalAlign, VerticalAlign};
+pub use resolve::AttachedGraphics;
+pub use bounds::Bounds;
+pub use canvas::{AddCanvas, CanvasGraphics};
+pub use contexts::{AddGraphicsContext, ContextGraphics, GraphicsContext};
+pub use space::{Anchor, CoordinateSpace, SpaceGraphics};
+pub use scope::ScopedGraphics;
+pub use lifetime::Lifetime;
+pub use groups::ShapeGroupHandle;
+pub use picking::ShapeId;
+pub use interaction::{ShapeClicked, ShapeDragged, ShapeHovered};
+pub use stats::{Immediate2DGraphicsStats, ShapeCounts, StatsOverlay};
+pub use queue::GraphicsQueue;
+pub use svg::SvgExport;
+pub use raster::Raster;
+#[cfg(feature = "serde")]
+pub use record::{FrameCommands, Recorder, Replayer};
+#[cfg(feature = "remote")]
+pub use remote::{RemoteSink, RemoteSource};
+
+#[macro_use]
+mod lifetime;
+#[macro_use]
+mod picking;
 mod circles;
 mod rectangles;
 mod lines;
+mod images;
+mod labels;
+mod textures;
+mod effects;
+mod state;
+mod transform;
+mod resolve;
+mod bounds;
+mod canvas;
+mod contexts;
+mod space;
+mod scope;
+mod groups;
+mod pool;
+mod queue;
+mod svg;
+mod raster;
+mod interaction;
+mod stats;
+#[cfg(feature = "serde")]
+mod record;
+#[cfg(feature = "remote")]
+mod remote;
+pub mod snapshot;
 
 #[derive(Debug, Default)]
 pub struct Immediate2DGraphics {
     circles: Vec<Circle>,
     rectangles: Vec<Rectangle>,
     lines: Vec<Line>,
+    images: Vec<Image>,
+    labels: Vec<Label>,
+    /// Font used by text that does not define its own.
+    default_font: Option<Handle<Font>>,
+    /// Number of shapes submitted this frame, used to order them.
+    depth: u32,
+    /// Layer subsequently submitted shapes are drawn on.
+    layer: u32,
+    /// Transformation applied to every submitted shape.
+    transform: Mat3,
+    transform_stack: Vec<Mat3>,
+    /// Entity subsequently submitted shapes are attached to.
+    attachment: Option<Entity>,
+    resolver: Resolver,
+    /// Rectangle subsequently submitted shapes are clipped to.
+    clip: Option<Bounds>,
+    clip_stack: Vec<Option<Bounds>>,
+    /// Canvas subsequently submitted shapes are drawn into.
+    canvas: Option<Handle<Texture>>,
+    /// Context subsequently submitted shapes belong to.
+    context: Option<&'static str>,
+    /// Space subsequently submitted shapes are drawn in.
+    space: CoordinateSpace,
+    groups: Groups,
+    /// Shapes with an id drawn in the last frame.
+    picking: Picking,
 }
 
 impl Immediate2DGraphics {
     pub fn fill_circle(&mut self, x: f32, y: f32) -> CircleBuilder<'_> {
-        self.circles.push(Circle::new(x, y));
+        let state = self.next_state();
+        self.circles.push(Circle::new(state, x, y));
 
         CircleBuilder {
             graphics: self
@@ -25,37 +111,611 @@ impl Immediate2DGraphics {
     }
 
     pub fn fill_rectangle(&mut self, x: f32, y: f32) -> RectangleBuilder<'_> {
-        self.rectangles.push(Rectangle::new(x, y));
+        let state = self.next_state();
+        self.rectangles.push(Rectangle::new(state, x, y));
 
         RectangleBuilder {
             graphics: self
         }
     }
 
+    /// Draw only the outline of a circle.
+    pub fn stroke_circle(&mut self, x: f32, y: f32) -> CircleOutlineBuilder<'_> {
+        let state = self.next_state();
+        self.circles.push(Circle::new_outline(state, x, y));
+
+        CircleOutlineBuilder {
+            graphics: self
+        }
+    }
+
+    /// Draw only the outline of a rectangle.
+    pub fn stroke_rectangle(&mut self, x: f32, y: f32) -> RectangleOutlineBuilder<'_> {
+        let state = self.next_state();
+        self.rectangles.push(Rectangle::new_outline(state, x, y));
+
+        RectangleOutlineBuilder {
+            graphics: self
+        }
+    }
+
     pub fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) -> LineBuilder<'_> {
-        self.lines.push(Line::new(x1, y1, x2, y2));
+        let state = self.next_state();
+        self.lines.push(Line::new(state, x1, y1, x2, y2));
 
         LineBuilder {
             graphics: self
         }
     }
+
+    /// Draw a texture centered at the given position. Unless set with `with_size`, its size
+    /// in world units is its size in pixels. Canvases always need `with_size`, see `canvas`.
+    pub fn draw_image(&mut self, texture: Handle<Texture>, x: f32, y: f32) -> ImageBuilder<'_> {
+        let state = self.next_state();
+        self.images.push(Image::new(state, texture, x, y));
+
+        ImageBuilder {
+            graphics: self
+        }
+    }
+
+    /// Draw text, by default with its baseline starting at the given position.
+    pub fn draw_text(&mut self, text: &str, x: f32, y: f32) -> LabelBuilder<'_> {
+        let state = self.next_state();
+        self.labels.push(Label::new(state, text.to_string(), x, y));
+
+        LabelBuilder {
+            graphics: self
+        }
+    }
+
+    /// Define the font used by text drawn without `with_font`.
+    pub fn set_default_font(&mut self, font: Handle<Font>) {
+        self.default_font = Some(font);
+    }
+
+    /// Save the current transformation, to be restored by `pop_transform`.
+    pub fn push_transform(&mut self) -> &mut Self {
+        self.transform_stack.push(self.transform);
+        self
+    }
+
+    /// Restore the transformation saved by the last `push_transform`.
+    pub fn pop_transform(&mut self) -> &mut Self {
+        self.transform = self.transform_stack.pop().unwrap_or_else(Mat3::identity);
+        self
+    }
+
+    /// Move all subsequently submitted shapes.
+    pub fn translate(&mut self, x: f32, y: f32) -> &mut Self {
+        self.transform = self.transform * transform::affine(Vec2::one(), 0., Vec2::new(x, y));
+        self
+    }
+
+    /// Rotate all subsequently submitted shapes around the current origin.
+    pub fn rotate(&mut self, rotation: f32) -> &mut Self {
+        self.transform = self.transform * transform::affine(Vec2::one(), rotation, Vec2::zero());
+        self
+    }
+
+    /// Scale all subsequently submitted shapes around the current origin.
+    /// Circles become ellipses when scaled non-uniformly.
+    pub fn scale(&mut self, x: f32, y: f32) -> &mut Self {
+        self.transform = self.transform * transform::affine(Vec2::new(x, y), 0., Vec2::zero());
+        self
+    }
+
+    /// Run `draw` with its own transformation, which is restored afterwards.
+    pub fn with_transform(&mut self, draw: impl FnOnce(&mut Self)) -> &mut Self {
+        self.push_transform();
+        draw(self);
+        self.pop_transform()
+    }
+
+    /// Clip all subsequently submitted shapes to a rectangle, until `pop_clip` is called.
+    /// The rectangle is transformed by the current transformation and
+    /// intersected with the current clip rectangle. Text is not clipped.
+    pub fn push_clip_rect(&mut self, rect: Bounds) -> &mut Self {
+        let rect = rect.transformed(&self.transform);
+        let clip = match self.clip {
+            Some(clip) => clip.intersection(&rect),
+            None => rect,
+        };
+
+        self.clip_stack.push(self.clip);
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Restore the clip rectangle active before the last `push_clip_rect`.
+    pub fn pop_clip(&mut self) -> &mut Self {
+        self.clip = self.clip_stack.pop().unwrap_or(None);
+        self
+    }
+
+    /// Draw shapes in the local space of an entity, following its
+    /// `Translation`, `Rotation` and `Scale` as they are when the shapes are rendered.
+    /// Shapes attached to a child entity follow its world `Transform`, including its parents.
+    /// Shapes of entities that got despawned are not drawn.
+    pub fn attached_to(&mut self, entity: Entity) -> AttachedGraphics<'_> {
+        ScopedGraphics::replace(self, |graphics| &mut graphics.attachment, Some(entity))
+    }
+
+    /// Draw shapes into a canvas registered with `AddCanvas::add_canvas`
+    /// instead of the screen. The texture of the canvas can then be drawn
+    /// like any other, e.g. with `draw_image`, but not into itself.
+    /// The texture of a canvas is not in `Assets<Texture>`, so its size is unknown to
+    /// `draw_image` and has to be given with `with_size`. Without it, nothing is drawn.
+    ///
+    /// ```ignore
+    /// // with app.add_canvas(texture, 256, 256)
+    /// graphics.canvas(texture).fill_circle(0., 0.).with_radius(64.);
+    /// // shows the canvas on a 2 by 2 unit square.
+    /// graphics.draw_image(texture, 0., 0.).with_size(2., 2.);
+    /// ```
+    pub fn canvas(&mut self, texture: Handle<Texture>) -> CanvasGraphics<'_> {
+        ScopedGraphics::replace(self, |graphics| &mut graphics.canvas, Some(texture))
+    }
+
+    /// Draw shapes in a named context, rendered only by the camera bound to it
+    /// with a `GraphicsContext`, e.g. one added by `AddGraphicsContext::add_graphics_context`.
+    /// Shapes outside of any context are rendered by the default 2d camera.
+    pub fn context(&mut self, name: &'static str) -> ContextGraphics<'_> {
+        ScopedGraphics::replace(self, |graphics| &mut graphics.context, Some(name))
+    }
+
+    /// Draw shapes in another coordinate space, e.g. in pixels for a HUD.
+    /// Screen space coordinates are resolved against the viewport of the camera
+    /// rendering the shapes when they are rendered, so they follow window resizes.
+    pub fn in_space(&mut self, space: CoordinateSpace) -> SpaceGraphics<'_> {
+        ScopedGraphics::replace(self, |graphics| &mut graphics.space, space)
+    }
+
+    /// Draw subsequently submitted shapes and groups on a layer, on top of all lower layers.
+    /// Shapes are drawn on layer 0 by default.
+    ///
+    /// Layers go up to 99, higher layers would be behind the far plane of the 2d camera,
+    /// so they are clamped to 99. Shapes are stacked above their layer in submission order,
+    /// so after 10,000 shapes in a frame, later shapes reach into the layers above.
+    /// Layer 99 is reserved for the statistics overlay, use lower layers to stay below it.
+    pub fn set_layer(&mut self, layer: u32) -> &mut Self {
+        debug_assert!(layer <= MAX_LAYER, "layer {} is behind the camera, the highest is {}", layer, MAX_LAYER);
+        self.layer = layer.min(MAX_LAYER);
+        self
+    }
+
+    /// Submit shapes once and keep drawing them until the group is removed.
+    /// The shapes are spawned as entities once, so they are not resubmitted every frame.
+    /// They are drawn on the current layer and resolved against the current state
+    /// when spawned, so they do not follow attached entities or window resizes.
+    /// Text is not supported and dropped, images need their texture to be loaded.
+    pub fn retain(&mut self, draw: impl FnOnce(&mut Immediate2DGraphics)) -> ShapeGroupHandle {
+        let mut group = Immediate2DGraphics {
+            default_font: self.default_font,
+            layer: self.layer,
+            transform: self.transform,
+            attachment: self.attachment,
+            clip: self.clip,
+            canvas: self.canvas,
+            context: self.context,
+            space: self.space,
+            ..Default::default()
+        };
+        draw(&mut group);
+        group.labels.clear();
+
+        self.groups.add(group)
+    }
+
+    /// Show or hide a retained group.
+    pub fn set_group_visible(&mut self, group: ShapeGroupHandle, visible: bool) -> &mut Self {
+        self.groups.set_visible(group, visible);
+        self
+    }
+
+    /// Transform a retained group in world space, replacing its previous transformation.
+    pub fn set_group_transform(&mut self, group: ShapeGroupHandle, transform: Mat3) -> &mut Self {
+        self.groups.set_transform(group, transform);
+        self
+    }
+
+    /// Stop drawing a retained group and despawn its entities.
+    pub fn remove_group(&mut self, group: ShapeGroupHandle) -> &mut Self {
+        self.groups.remove(group);
+        self
+    }
+
+    /// Ids of the shapes drawn in the last frame that contain a point in world units,
+    /// the topmost first. Outlines are only hit on their outline.
+    pub fn hit_test(&self, point: Vec2) -> Vec<ShapeId> {
+        self.picking.hit_test(point)
+    }
+
+    /// Ids of the shapes drawn in the last frame whose bounds overlap a rectangle in world units,
+    /// the topmost first.
+    pub fn shapes_in_rect(&self, rect: Bounds) -> Vec<ShapeId> {
+        self.picking.shapes_in_rect(rect)
+    }
+
+    /// Bounds in world units of the circles, rectangles, lines and images submitted so far,
+    /// `None` if there are none. Attached and screen space shapes use the transformations
+    /// from the last frame. Images without a size set are left out, like text.
+    pub fn frame_bounds(&self) -> Option<Bounds> {
+        let resolver = &self.resolver;
+        resolved_bounds(&self.circles, resolver, |circle| &mut circle.state, |circle| Some(circle.bounds()))
+            .chain(resolved_bounds(&self.rectangles, resolver, |rectangle| &mut rectangle.state, |rectangle| Some(rectangle.bounds())))
+            .chain(resolved_bounds(&self.lines, resolver, |line| &mut line.state, |line| Some(line.bounds())))
+            .chain(resolved_bounds(&self.images, resolver, |image| &mut image.state, Image::bounds))
+            .filter(|bounds| !bounds.is_empty())
+            .fold(None, |frame: Option<Bounds>, bounds| Some(frame.map_or(bounds, |frame| frame.union(&bounds))))
+    }
+
+    /// Serialize the shapes submitted so far to an SVG document showing `viewport`,
+    /// which is given in world units. Textures and images are left out, and shadows and glows
+    /// are drawn with blur filters, which soften their edges a little differently.
+    /// Attached shapes use the transformations of their entities from the last frame.
+    pub fn to_svg(&self, viewport: Bounds) -> String {
+        svg::to_svg(self, viewport)
+    }
+
+    /// Render the shapes submitted so far on the cpu into an image of `width` by `height`
+    /// pixels showing `viewport`, following the semantics of the shape shaders.
+    /// Text is left out. Textures are sampled without filtering.
+    pub fn rasterize(&self, viewport: Bounds, width: u32, height: u32, textures: &Assets<Texture>) -> Raster {
+        raster::rasterize(self, viewport, width, height, textures)
+    }
+
+    /// States of all submitted shapes.
+    pub(crate) fn states(&self) -> impl Iterator<Item = &ShapeState> {
+        self.circles.iter().map(|circle| &circle.state)
+            .chain(self.rectangles.iter().map(|rectangle| &rectangle.state))
+            .chain(self.lines.iter().map(|line| &line.state))
+            .chain(self.images.iter().map(|image| &image.state))
+            .chain(self.labels.iter().map(|label| &label.state))
+    }
+
+    /// Moves the shapes of another graphics into these, drawing them on top of
+    /// the shapes submitted so far on their layer.
+    pub(crate) fn append(&mut self, other: &mut Immediate2DGraphics) {
+        let offset = self.depth as f32 * DEPTH_STEP;
+        for state in other.states_mut() {
+            state.z += offset;
+        }
+
+        self.circles.append(&mut other.circles);
+        self.rectangles.append(&mut other.rectangles);
+        self.lines.append(&mut other.lines);
+        self.images.append(&mut other.images);
+        self.labels.append(&mut other.labels);
+        self.groups.append(&mut other.groups);
+        self.depth += other.depth;
+
+        other.reset_state();
+    }
+
+    fn states_mut(&mut self) -> impl Iterator<Item = &mut ShapeState> {
+        self.circles.iter_mut().map(|circle| &mut circle.state)
+            .chain(self.rectangles.iter_mut().map(|rectangle| &mut rectangle.state))
+            .chain(self.lines.iter_mut().map(|line| &mut line.state))
+            .chain(self.images.iter_mut().map(|image| &mut image.state))
+            .chain(self.labels.iter_mut().map(|label| &mut label.state))
+    }
+
+    /// Restarts the submission order and transformation.
+    fn reset_state(&mut self) {
+        self.depth = 0;
+        self.layer = 0;
+        self.transform = Mat3::identity();
+        self.transform_stack.clear();
+        self.attachment = None;
+        self.clip = None;
+        self.clip_stack.clear();
+        self.canvas = None;
+        self.context = None;
+        self.space = CoordinateSpace::World;
+    }
+
+    /// Gives the shapes kept for the next frame a new z in the order they were drawn,
+    /// so shapes submitted next frame are drawn on top of them on their layer.
+    fn restamp_kept_shapes(&mut self) {
+        let mut states: Vec<&mut ShapeState> = self.states_mut().collect();
+        states.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap_or(std::cmp::Ordering::Equal));
+
+        let kept = states.len();
+        for (depth, state) in states.into_iter().enumerate() {
+            let layer = (state.z / LAYER_DEPTH).floor();
+            state.z = layer * LAYER_DEPTH + depth as f32 * DEPTH_STEP;
+        }
+        self.depth = kept as u32;
+    }
+
+    /// Captures the state for the next submitted shape.
+    /// Every shape is placed slightly above the previous one.
+    fn next_state(&mut self) -> ShapeState {
+        let z = self.layer as f32 * LAYER_DEPTH + self.depth as f32 * DEPTH_STEP;
+        self.depth += 1;
+
+        ShapeState {
+            z,
+            transform: self.transform,
+            attached_to: self.attachment,
+            clip: self.clip,
+            canvas: self.canvas,
+            context: self.context,
+            space: self.space,
+            lifetime: None,
+            id: None,
+            interactive: false,
+        }
+    }
 }
 
+/// World space bounds of the shapes that can be resolved.
+fn resolved_bounds<'a, T: Clone>(
+    shapes: &'a [T],
+    resolver: &'a Resolver,
+    state: fn(&mut T) -> &mut ShapeState,
+    bounds: fn(&T) -> Option<Bounds>,
+) -> impl Iterator<Item = Bounds> + 'a {
+    shapes.iter().filter_map(move |shape| {
+        let mut shape = shape.clone();
+        if resolver.resolve(state(&mut shape)) {
+            bounds(&shape)
+        } else {
+            None
+        }
+    })
+}
+
+/// Spawns the default 2d camera, which renders all shapes outside of a context.
+fn setup_camera(mut commands: Commands) {
+    commands.spawn(Camera2dComponents::default());
+}
+
+/// Keeps the shapes whose lifetime is not over yet.
+fn tick_shapes<T>(shapes: &mut Vec<T>, delta: f32, state: fn(&mut T) -> &mut ShapeState) {
+    let alive = shapes.drain(..)
+        .filter_map(|mut shape| if state(&mut shape).tick(delta) { Some(shape) } else { None })
+        .collect();
+    *shapes = alive;
+}
+
+/// Removes the shapes drawn this frame, except persistent ones,
+/// and restarts the submission order and transformation for the next frame.
+fn frame_end_system(time: Res<Time>, mut immediate_graphics: ResMut<Immediate2DGraphics>) {
+    let delta = time.delta_seconds;
+    let graphics = &mut *immediate_graphics;
+    tick_shapes(&mut graphics.circles, delta, |circle| &mut circle.state);
+    tick_shapes(&mut graphics.rectangles, delta, |rectangle| &mut rectangle.state);
+    tick_shapes(&mut graphics.lines, delta, |line| &mut line.state);
+    tick_shapes(&mut graphics.images, delta, |image| &mut image.state);
+    tick_shapes(&mut graphics.labels, delta, |label| &mut label.state);
+
+    graphics.groups.clear_spawned();
+    graphics.reset_state();
+    graphics.restamp_kept_shapes();
+}
+
+/// Stage in which submitted shapes are turned into entities, right after `stage::POST_UPDATE`.
+/// Shapes submitted in any earlier stage, including `stage::POST_UPDATE`, are drawn the same frame.
+/// Shape entities do not depend on the transform systems, and the entities visible to each
+/// camera are updated with the shapes of the frame by `shape_visibility_system`.
+pub const GRAPHICS_STAGE: &str = "immediate_2d_graphics";
+
 pub struct Immediate2DGraphicsPlugin;
 
 impl Plugin for Immediate2DGraphicsPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_resource::<Immediate2DGraphics>()
-            // It only works if stage is UPDATE, POST_UPDATE only works sometimes.
-            // There has to be some system that interferes with there.
-            .add_system_to_stage(stage::UPDATE, circles::circle_update_system.system())
-            .add_system_to_stage(stage::UPDATE, rectangles::rectangle_update_system.system())
-            .add_system_to_stage(stage::UPDATE, lines::line_update_system.system());
+        add_systems(app);
+
+        app.add_startup_system(setup_camera.system())
+            .add_system_to_stage(bevy::render::stage::RENDER_RESOURCE, contexts::shape_visibility_system.system())
+            .add_system_to_stage(bevy::render::stage::DRAW, labels::label_draw_system.system());
 
         let resources = app.resources();
 
+        textures::add_render_graph(&*resources);
         circles::add_render_graph(&*resources);
         rectangles::add_render_graph(&*resources);
         lines::add_render_graph(&*resources);
+        images::add_render_graph(&*resources);
+    }
+}
+
+/// Adds the resources, stage and systems of the plugin that do not need the renderer.
+fn add_systems(app: &mut AppBuilder) {
+    app.init_resource::<Immediate2DGraphics>()
+        .init_resource::<GraphicsQueue>()
+        .init_resource::<SvgExport>()
+        .init_resource::<Immediate2DGraphicsStats>()
+        .init_resource::<StatsOverlay>()
+        .add_event::<ShapeHovered>()
+        .add_event::<ShapeClicked>()
+        .add_event::<ShapeDragged>()
+        // sends the events before stage::UPDATE, for the shapes the user sees.
+        .add_system_to_stage(stage::PRE_UPDATE, interaction::interaction_system.system())
+        .add_stage_after(stage::POST_UPDATE, GRAPHICS_STAGE)
+        // all of these access Immediate2DGraphics or its statistics mutably,
+        // so they run in the order they are added.
+        // the overlay shows the statistics of the last frame, before they are restarted.
+        .add_system_to_stage(GRAPHICS_STAGE, stats::stats_overlay_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, stats::stats_begin_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, queue::queue_merge_system.system());
+
+    #[cfg(feature = "serde")]
+    app.init_resource::<Recorder>()
+        .init_resource::<Replayer>()
+        .add_system_to_stage(GRAPHICS_STAGE, record::replay_system.system());
+
+    #[cfg(feature = "remote")]
+    app.init_resource::<RemoteSink>()
+        .init_resource::<RemoteSource>()
+        .add_system_to_stage(GRAPHICS_STAGE, remote::remote_source_system.system());
+
+    app.add_system_to_stage(GRAPHICS_STAGE, resolve::resolve_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, svg::svg_export_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, picking::picking_system.system());
+
+    #[cfg(feature = "serde")]
+    app.add_system_to_stage(GRAPHICS_STAGE, record::record_system.system());
+
+    #[cfg(feature = "remote")]
+    app.add_system_to_stage(GRAPHICS_STAGE, remote::remote_sink_system.system());
+
+    app.add_system_to_stage(GRAPHICS_STAGE, circles::circle_update_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, rectangles::rectangle_update_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, lines::line_update_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, images::image_update_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, labels::label_update_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, groups::group_system.system())
+        .add_system_to_stage(GRAPHICS_STAGE, frame_end_system.system());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::Component;
+    use bevy::render::camera::ActiveCameras;
+    use bevy::sprite::TextureAtlas;
+    use bevy::text::FontAtlasSet;
+    use bevy::window::CursorMoved;
+    use circles::CircleStyle;
+    use rectangles::RectangleStyle;
+
+    /// App with the systems of the plugin, but without a renderer or a window.
+    fn app() -> App {
+        let mut builder = App::build();
+        builder.init_resource::<Time>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Windows>()
+            .init_resource::<Assets<Texture>>()
+            .init_resource::<Assets<Font>>()
+            .init_resource::<Assets<FontAtlasSet>>()
+            .init_resource::<Assets<TextureAtlas>>()
+            .add_event::<CursorMoved>();
+        add_systems(&mut builder);
+
+        builder.add_system_to_stage(stage::UPDATE, draw_in_update.system())
+            .add_system_to_stage(stage::POST_UPDATE, draw_in_post_update.system())
+            // scheduled in the reverse order of their keys.
+            .add_system_to_stage(stage::UPDATE, queue_b.system())
+            .add_system_to_stage(stage::UPDATE, queue_a.system());
+        builder.app
+    }
+
+    fn draw_in_update(mut graphics: ResMut<Immediate2DGraphics>) {
+        graphics.fill_rectangle(100., 0.);
+    }
+
+    fn draw_in_post_update(mut graphics: ResMut<Immediate2DGraphics>) {
+        graphics.fill_circle(-100., 0.);
+    }
+
+    fn queue_b(queue: Res<GraphicsQueue>) {
+        queue.draw("b", |graphics| {
+            graphics.fill_rectangle(0., 20.).with_width(10.).with_height(10.);
+        });
+    }
+
+    fn queue_a(queue: Res<GraphicsQueue>) {
+        queue.draw("a", |graphics| {
+            graphics.fill_rectangle(0., 10.).with_width(10.).with_height(10.);
+        });
+    }
+
+    /// Number of entities with the given style, and how many of them are visible.
+    fn count<T: Component>(app: &App) -> (usize, usize) {
+        let mut query = app.world.query::<(&Draw, &T)>();
+        query.iter().fold((0, 0), |(all, visible), (draw, _)| (all + 1, visible + draw.is_visible as usize))
+    }
+
+    /// Depth of the visible rectangle at a height.
+    fn rectangle_z(app: &App, y: f32) -> f32 {
+        let mut query = app.world.query::<(&Draw, &Transform, &RectangleStyle)>();
+        query.iter()
+            .filter(|(draw, transform, _)| draw.is_visible && transform.value.w_axis().y() == y)
+            .map(|(_, transform, _)| transform.value.w_axis().z())
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn shapes_are_drawn_the_frame_they_are_submitted() {
+        let mut app = app();
+
+        for frame in 0..3 {
+            app.update();
+
+            // entities are spawned for the first frame and reused after.
+            assert_eq!(count::<CircleStyle>(&app), (1, 1), "frame {}", frame);
+            assert_eq!(count::<RectangleStyle>(&app), (3, 3), "frame {}", frame);
+
+            // nothing is left over for the next frame.
+            let graphics = app.resources.get::<Immediate2DGraphics>().unwrap();
+            assert_eq!(graphics.states().count(), 0, "frame {}", frame);
+        }
+    }
+
+    #[test]
+    fn queue_buffers_are_merged_in_key_order() {
+        let mut app = app();
+
+        for _ in 0..3 {
+            app.update();
+
+            // the shapes of "b" are merged last, so they are on top.
+            assert!(rectangle_z(&app, 20.) > rectangle_z(&app, 10.));
+        }
+    }
+
+    #[test]
+    fn canvas_cameras_are_active() {
+        let texture = Handle::<Texture>::new();
+
+        // the render graph of the plugin, without a window or a renderer backend.
+        let mut builder = App::build();
+        builder.add_resource(Msaa::default())
+            .add_plugin(bevy::type_registry::TypeRegistryPlugin::default())
+            .add_plugin(bevy::core::CorePlugin::default())
+            .add_plugin(bevy::transform::TransformPlugin)
+            .add_plugin(bevy::window::WindowPlugin::default())
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_plugin(bevy::render::RenderPlugin::default())
+            .add_plugin(Immediate2DGraphicsPlugin)
+            .add_canvas(texture, 64, 64);
+
+        // cameras that are not active get no view and draw nothing.
+        let camera = canvas::canvas_name(texture, "camera");
+        let active_cameras = builder.resources().get::<ActiveCameras>().unwrap();
+        assert!(active_cameras.cameras.contains_key(&camera));
+    }
+
+    #[test]
+    fn untinted_textures_fade_out() {
+        let texture = Handle::<Texture>::new();
+        let textures = Assets::<Texture>::default();
+        let mut graphics = Immediate2DGraphics::default();
+        graphics.fill_circle(0., 0.).with_texture(texture).for_frames(4).fading_out();
+        graphics.fill_rectangle(0., 0.).with_texture(texture).for_frames(4).fading_out();
+        // one of four frames is over.
+        for state in graphics.states_mut() {
+            state.lifetime.as_mut().unwrap().tick(0.);
+        }
+
+        let faded_white = Color::rgba(1., 1., 1., 0.75);
+        let circle = circles::prepare_circle(graphics.circles[0].clone(), &textures);
+        assert_eq!(circle.style.fill_color, faded_white);
+        let rectangle = rectangles::prepare_rectangle(graphics.rectangles[0].clone(), &textures);
+        assert_eq!(rectangle.style.fill_color, faded_white);
+    }
+
+    #[test]
+    fn lines_far_from_the_origin() {
+        let transform = lines::calc_transform_for_line(Vec2::new(10000., 0.), Vec2::new(10010., 0.), 1., 0.);
+        // the corner (1, 1) of the quad lies a stroke past the end of the line, on its left.
+        let corner = transform * Vec4::new(1., 1., 0., 1.);
+        assert!((corner.x() - 10011.).abs() < 0.01, "{:?}", corner);
+        assert!((corner.y() - 1.).abs() < 0.01, "{:?}", corner);
     }
 }
@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Remaining {
+    Frames { left: u32, total: u32 },
+    Seconds { left: f32, total: f32 },
+    /// Ends after the current frame, keeping the opacity it had when it was frozen.
+    Frozen { opacity: f32 },
+}
+
+/// How long a shape keeps being drawn after it was submitted.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lifetime {
+    remaining: Remaining,
+    /// Whether the alpha of the shape decreases over its lifetime.
+    pub fade: bool,
+}
+
+impl Lifetime {
+    pub fn frames(frames: u32) -> Self {
+        Self {
+            remaining: Remaining::Frames { left: frames, total: frames },
+            fade: false,
+        }
+    }
+
+    pub fn duration(duration: Duration) -> Self {
+        let seconds = duration.as_secs_f32();
+        Self {
+            remaining: Remaining::Seconds { left: seconds, total: seconds },
+            fade: false,
+        }
+    }
+
+    /// Keeps fading out if the lifetime it replaces did.
+    pub(crate) fn fading(mut self, previous: Option<Lifetime>) -> Self {
+        self.fade |= previous.map_or(false, |previous| previous.fade);
+        self
+    }
+
+    /// Counts down a frame that took `delta` seconds. Returns false once the lifetime is over.
+    pub(crate) fn tick(&mut self, delta: f32) -> bool {
+        match &mut self.remaining {
+            Remaining::Frames { left, .. } => {
+                *left = left.saturating_sub(1);
+                *left > 0
+            }
+            Remaining::Seconds { left, .. } => {
+                *left -= delta;
+                *left > 0.
+            }
+            Remaining::Frozen { .. } => false,
+        }
+    }
+
+    /// Lifetime ending after the current frame, with the opacity this one has now.
+    pub(crate) fn frozen(&self) -> Self {
+        Self {
+            remaining: Remaining::Frozen { opacity: self.opacity() },
+            fade: self.fade,
+        }
+    }
+
+    /// Factor the alpha of the shape is multiplied with.
+    pub(crate) fn opacity(&self) -> f32 {
+        if !self.fade {
+            return 1.;
+        }
+
+        match self.remaining {
+            Remaining::Frames { left, total } => left as f32 / total.max(1) as f32,
+            Remaining::Seconds { left, total } if total > 0. => (left / total).max(0.),
+            Remaining::Seconds { .. } => 1.,
+            Remaining::Frozen { opacity } => opacity,
+        }
+    }
+}
+
+pub(crate) fn fade(color: Color, opacity: f32) -> Color {
+    Color::rgba(color.r, color.g, color.b, color.a * opacity)
+}
+
+/// Adds `for_duration`, `for_frames` and `fading_out` to the builder of a kind of shape,
+/// operating on the last shape in the given field of `Immediate2DGraphics`.
+macro_rules! impl_lifetime {
+    ($builder:ident, $shapes:ident) => {
+        impl<'a> $builder<'a> {
+            /// Keep drawing the shape for a duration instead of only this frame.
+            pub fn for_duration(self, duration: std::time::Duration) -> Self {
+                let shape = self.graphics.$shapes.last_mut().unwrap();
+                shape.state.lifetime = Some(crate::lifetime::Lifetime::duration(duration)
+                    .fading(shape.state.lifetime));
+                self
+            }
+
+            /// Keep drawing the shape for a number of frames, including this one.
+            pub fn for_frames(self, frames: u32) -> Self {
+                let shape = self.graphics.$shapes.last_mut().unwrap();
+                shape.state.lifetime = Some(crate::lifetime::Lifetime::frames(frames)
+                    .fading(shape.state.lifetime));
+                self
+            }
+
+            /// Fade the shape out over its lifetime, set by `for_duration` or `for_frames`
+            /// before or after this.
+            pub fn fading_out(self) -> Self {
+                let shape = self.graphics.$shapes.last_mut().unwrap();
+                // a lifetime of one frame is the same as none, until a longer one is set.
+                let lifetime = shape.state.lifetime.get_or_insert_with(|| crate::lifetime::Lifetime::frames(1));
+                lifetime.fade = true;
+                self
+            }
+        }
+    };
+}
@@ -9,17 +9,32 @@ use bevy::{
         render_graph::{RenderGraph, base, RenderResourcesNode}, mesh::VertexAttribute
     },
     core::Byteable,
+    ecs::Without,
     prelude::*, math::Mat2,
 };
 use std::ops::{DerefMut, Deref};
+use std::time::Instant;
 use super::Immediate2DGraphics;
+use super::stats::Immediate2DGraphicsStats;
+use super::state::ShapeState;
+use super::textures;
+use super::transform;
+use super::bounds;
+use super::lifetime;
+use super::canvas::CanvasPass;
+use super::contexts::ShapeTarget;
+use super::groups::RetainedShape;
+use super::pool::{PooledShape, ShapePool};
 use base::MainPass;
 
 #[derive(Clone, Debug, RenderResources, RenderResource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[render_resources(from_self)]
 #[allow(unused)]
+#[repr(C)]
 pub struct LineStyle {
     pub color: Color,
+    pub clip: Vec4,
     pub width: f32,
     pub height: f32,
     pub stroke: f32,
@@ -30,6 +45,7 @@ impl Default for LineStyle {
     fn default() -> Self {
         Self {
             color: Color::BLACK,
+            clip: bounds::clip_uniform(&None),
             width: 0.,
             height: 0.,
             stroke: 10.,
@@ -37,21 +53,40 @@ impl Default for LineStyle {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Line {
+    pub state: ShapeState,
     pub style: LineStyle,
     pub start: Vec2,
     pub stop: Vec2,
 }
 
 impl Line {
-    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+    pub fn new(state: ShapeState, x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
         Self {
+            state,
             style: LineStyle::default(),
             start: Vec2::new(x1, y1),
             stop: Vec2::new(x2, y2),
         }
     }
+
+    /// Whether the line has no length or no stroke, so it covers nothing
+    /// and no transformation can be computed for its quad.
+    pub(crate) fn is_degenerate(&self) -> bool {
+        (self.stop - self.start).length_squared() <= f32::EPSILON || self.style.stroke <= 0.
+    }
+
+    /// Bounds of the line and its round caps in the space it is transformed into,
+    /// usually world units, limited to its clip rectangle.
+    pub fn bounds(&self) -> bounds::Bounds {
+        let affine = &self.state.transform;
+        let start = transform::transform_point(affine, self.start);
+        let stop = transform::transform_point(affine, self.stop);
+        let stroke = Vec2::splat(self.style.stroke * transform::scale_factor(affine));
+        self.state.clipped(bounds::Bounds::new(start.min(stop) - stroke, start.max(stop) + stroke))
+    }
 }
 
 pub struct LineBuilder<'a> {
@@ -72,6 +107,9 @@ impl<'a> LineBuilder<'a> {
     }
 }
 
+impl_lifetime!(LineBuilder, lines);
+impl_shape_id!(LineBuilder, lines);
+
 impl<'a> Deref for LineBuilder<'a> {
     type Target = Immediate2DGraphics;
     fn deref(&self) -> &Self::Target {
@@ -98,7 +136,7 @@ impl<'a> Drop for LineBuilder<'a> {
 
 const LINE_PIPELINE: Handle<PipelineDescriptor> = Handle::from_u128(67859415639327501719432658702909922763);
 
-const LINE_STYLE_NODE: &str = "LineStyle";
+pub(crate) const LINE_STYLE_NODE: &str = "LineStyle";
 
 pub fn add_render_graph(resources: &Resources) {
         let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
@@ -117,7 +155,7 @@ pub fn add_render_graph(resources: &Resources) {
         let fragment_shader_handle = shaders.add(
             Shader::from_glsl(ShaderStage::Fragment, include_str!("shader/line.frag"))
         );
-        let pipeline_descriptor = PipelineDescriptor::default_config(ShaderStages {
+        let pipeline_descriptor = textures::shape_pipeline(ShaderStages {
             vertex: vertex_shader_handle,
             fragment: Some(fragment_shader_handle),
         });
@@ -164,63 +202,40 @@ struct LineComponents {
     pub draw: Draw,
     pub render_pipelines: RenderPipelines,
     pub main_pass: MainPass,
+    pub canvas_pass: CanvasPass,
+    pub target: ShapeTarget,
     pub transform: Transform,
 }
 
-fn calc_transformation_matrix(from: [Vec2; 4], to: [Vec2; 4]) -> Mat4 {
-    // ToMat = M * FromMat
-    // ToMat * FromMat^-1 = M
-    // Using last coordinate to verify result.
-
-    let from_mat = Mat3::from_cols(
-        Vec3::new(from[0].x(), from[0].y(), 1.),
-        Vec3::new(from[1].x(), from[1].y(), 1.),
-        Vec3::new(from[2].x(), from[2].y(), 1.),
-    );
-
-    let to_mat = Mat3::from_cols(
-        Vec3::new(to[0].x(), to[0].y(), 1.),
-        Vec3::new(to[1].x(), to[1].y(), 1.),
-        Vec3::new(to[2].x(), to[2].y(), 1.),
-    );
-
-    let result = to_mat * from_mat.inverse();
-
-    assert!(result.z_axis().z() - 1. <= f32::EPSILON);
-
-    let from_test = Vec3::new(from[3].x(), from[3].y(), 1.);
-    let to_test = Vec3::new(to[3].x(), to[3].y(), 1.);
-
-    let len = (result * from_test - to_test).length_squared();
-
-    assert!(len <= f32::EPSILON);
-
-    // result:
-    // a b t
-    // c d p
-    // 0 0 1
-    //
-    // return
-    // a b 0 t
-    // c d 0 p
-    // 0 0 0 0
-    // 0 0 0 1
-
-    let x_axis = Vec4::new(result.x_axis().x(), result.x_axis().y(), 0., 0.);
-    let y_axis = Vec4::new(result.y_axis().x(), result.y_axis().y(), 0., 0.);
-    let z_axis = Vec4::new(0., 0., 0., 0.);
-    let w_axis = Vec4::new(result.z_axis().x(), result.z_axis().y(), 0., 1.);
-    Mat4::from_cols(x_axis, y_axis, z_axis, w_axis)
+/// Maps the unit quad of the line mesh onto the corners of a line. `origin` is the corner
+/// of (0, 0), `x_axis` and `y_axis` are the edges towards (1, 0) and (0, 1).
+fn quad_matrix(origin: Vec2, x_axis: Vec2, y_axis: Vec2) -> Mat4 {
+    Mat4::from_cols(
+        Vec4::new(x_axis.x(), x_axis.y(), 0., 0.),
+        Vec4::new(y_axis.x(), y_axis.y(), 0., 0.),
+        Vec4::new(0., 0., 0., 0.),
+        Vec4::new(origin.x(), origin.y(), 0., 1.),
+    )
 }
 
-fn calc_transform_for_line(start: Vec2, end: Vec2, stroke: f32) -> Mat4 {
-    let from = [
-        Vec2::new(0., 0.),
-        Vec2::new(1., 1.),
-        Vec2::new(0., 1.),
-        Vec2::new(1., 0.),
-    ];
+/// Applies the transformation of the transform stack to the end points and stroke
+/// and writes the clipping and fading of the line.
+/// Lines stay round capped, so non-uniform scaling only affects their length.
+pub(crate) fn transform_line(mut line: Line) -> Line {
+    line.style.clip = bounds::clip_uniform(&line.state.clip);
+    line.style.color = lifetime::fade(line.style.color, line.state.opacity());
+
+    let affine = line.state.transform;
+    line.start = transform::transform_point(&affine, line.start);
+    line.stop = transform::transform_point(&affine, line.stop);
+
+    line.style.stroke *= transform::scale_factor(&affine);
+    line.style.width = (line.start - line.stop).length() + 2. * line.style.stroke;
+    line.style.height = 2. * line.style.stroke;
+    line
+}
 
+pub(crate) fn calc_transform_for_line(start: Vec2, end: Vec2, stroke: f32, z: f32) -> Mat4 {
     // calculate line bounding box.
 
     let dir = (end - start).normalize() * stroke;
@@ -239,24 +254,53 @@ fn calc_transform_for_line(start: Vec2, end: Vec2, stroke: f32) -> Mat4 {
     let to_2 = a + ccw_rotation * dir;
 
     let b = end + dir;
-    let to_1 = b + ccw_rotation * dir;
     let to_3 = b + cw_rotation * dir;
 
-    let to = [to_0, to_1, to_2, to_3];
+    // built from the corners instead of solved for, which loses precision far from the origin.
+    Mat4::from_translation(Vec3::new(0., 0., z)) * quad_matrix(to_0, to_3 - to_0, to_2 - to_0)
+}
+
+impl PooledShape for Line {
+    fn state(&self) -> &ShapeState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ShapeState {
+        &mut self.state
+    }
+
+    fn view_bounds(&self, _textures: &Assets<Texture>) -> Option<bounds::Bounds> {
+        Some(self.bounds())
+    }
+
+    fn prepare(self, _textures: &Assets<Texture>) -> Option<Self> {
+        let line = transform_line(self);
+        // the transform stack or an empty viewport can scale lines to nothing.
+        if line.is_degenerate() {
+            None
+        } else {
+            Some(line)
+        }
+    }
 
-    calc_transformation_matrix(from, to)
+    fn quad_transform(&self) -> Mat4 {
+        calc_transform_for_line(self.start, self.stop, self.style.stroke, self.state.z)
+    }
 }
 
 pub fn line_update_system(
     mut commands: Commands,
     mut immediate_graphics: ResMut<Immediate2DGraphics>,
-    mut query: Query<(
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    textures: Res<Assets<Texture>>,
+    mut query: Query<Without<RetainedShape, (
         &mut Draw,
         &mut LineStyle,
         &mut Transform,
-    )>,
+        &mut ShapeTarget,
+    )>>,
 ) {
-    commands.spawn(Camera2dComponents::default());
+    let start = Instant::now();
 
     let line_render_pipelines = || // Workaround because RenderPipelines is not Clone.
         RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
@@ -278,35 +322,60 @@ pub fn line_update_system(
         ]);
 
     let mut query_borrow = query.iter();
-    let mut query_iter = query_borrow.iter();
-
-    let mut lines_iter = immediate_graphics.lines.drain(..);
 
-    for (mut draw, mut style, mut transform) in &mut query_iter {
-        let line = if let Some(line) = lines_iter.next() {
-            line
-        } else {
-            draw.is_visible = false;
-            break;
-        };
+    let graphics = &mut *immediate_graphics;
+    let pending = graphics.groups.take_pending(|shapes| &mut shapes.lines);
+    let mut pool = ShapePool {
+        resolver: &graphics.resolver,
+        groups: &graphics.groups,
+        textures: &textures,
+        stats: &mut stats,
+    };
+
+    pool.update(
+        &graphics.lines,
+        pending,
+        query_borrow.iter(),
+        |(mut draw, mut style, mut transform, mut target), line| {
+            draw.is_visible = line.is_some();
+            if let Some((line, quad)) = line {
+                *style = line.style;
+                *target = ShapeTarget::of(&line.state);
+                *transform = quad;
+            }
+        },
+        |line, is_visible, transform, retained| {
+            let entity = spawn_line(&mut commands, line, line_render_pipelines(), is_visible, transform);
+            if let Some(retained) = retained {
+                entity.with(retained);
+            }
+        },
+    );
 
-        draw.is_visible = true;
-        *style = line.style.clone();
-        *transform = Transform::new(calc_transform_for_line(line.start, line.stop, line.style.stroke));
-    }
+    stats.submitted.lines = graphics.lines.len();
 
-    for (mut draw, _, _) in query_iter {
-        draw.is_visible = false;
-    }
+    stats.update_time += start.elapsed();
+}
 
-    for line in lines_iter {
-        commands.spawn(LineComponents {
-            mesh: LINE_QUAD_HANDLE,
-            render_pipelines: line_render_pipelines(),
-            transform: Transform::new(calc_transform_for_line(line.start, line.stop, line.style.stroke)),
+fn spawn_line<'a>(
+    commands: &'a mut Commands,
+    line: Line,
+    render_pipelines: RenderPipelines,
+    is_visible: bool,
+    transform: Transform,
+) -> &'a mut Commands {
+    commands.spawn(LineComponents {
+        mesh: LINE_QUAD_HANDLE,
+        draw: Draw {
+            is_visible,
+            is_transparent: true,
             ..Default::default()
-        })
-        .with(line.style);
-    }
+        },
+        render_pipelines,
+        transform,
+        target: ShapeTarget::of(&line.state),
+        ..Default::default()
+    })
+    .with(line.style)
 }
 
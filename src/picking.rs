@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+use super::Immediate2DGraphics;
+use super::bounds::Bounds;
+use super::state::ShapeState;
+use super::contexts::ShapeTarget;
+use super::images;
+use super::lines;
+use super::transform;
+
+/// Identifier attached to a shape with `with_id`, returned by hit tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShapeId(pub u64);
+
+/// Area covered by a shape, in its local space.
+#[derive(Debug, Clone, Copy)]
+enum Area {
+    /// Ring around the origin, with an inner radius of zero if filled.
+    Circle { radius: f32, inner_radius: f32 },
+    /// Frame around the origin, with an inner half size of zero if filled.
+    Rectangle { half_size: Vec2, inner_half_size: Vec2 },
+    /// Points closer to the segment than `radius`, giving round caps.
+    Segment { start: Vec2, stop: Vec2, radius: f32 },
+}
+
+impl Area {
+    fn contains(&self, point: Vec2) -> bool {
+        match *self {
+            Area::Circle { radius, inner_radius } => {
+                let distance = point.length();
+                distance <= radius && (inner_radius <= 0. || distance >= inner_radius)
+            }
+            Area::Rectangle { half_size, inner_half_size } => {
+                let point = point.abs();
+                let inside = |half_size: Vec2| point.x() <= half_size.x() && point.y() <= half_size.y();
+                inside(half_size) && (inner_half_size.x() <= 0. || inner_half_size.y() <= 0. || !inside(inner_half_size))
+            }
+            Area::Segment { start, stop, radius } => {
+                let direction = stop - start;
+                let length_squared = direction.dot(direction);
+                let t = if length_squared > 0. {
+                    ((point - start).dot(direction) / length_squared).max(0.).min(1.)
+                } else {
+                    0.
+                };
+                (point - (start + direction * t)).length() <= radius
+            }
+        }
+    }
+
+    fn bounds(&self) -> Bounds {
+        match *self {
+            Area::Circle { radius, .. } => Bounds::from_center_size(Vec2::zero(), Vec2::splat(radius * 2.)),
+            Area::Rectangle { half_size, .. } => Bounds::from_center_size(Vec2::zero(), half_size * 2.),
+            Area::Segment { start, stop, radius } => Bounds::new(
+                start.min(stop) - Vec2::splat(radius),
+                start.max(stop) + Vec2::splat(radius),
+            ),
+        }
+    }
+}
+
+/// Resolved shape with an id, as it was drawn.
+#[derive(Debug, Clone)]
+pub(crate) struct Pickable {
+    pub id: ShapeId,
+    pub z: f32,
+    /// Transformations between the local space of the area and world space.
+    to_world: Mat3,
+    to_local: Mat3,
+    area: Area,
+    clip: Option<Bounds>,
+    pub interactive: bool,
+    /// Canvas and context the shape was drawn for, whose camera the cursor is converted with.
+    pub target: ShapeTarget,
+}
+
+impl Pickable {
+    fn new(state: &ShapeState, to_world: Mat3, area: Area) -> Option<Self> {
+        // shapes scaled to nothing can not be hit.
+        if to_world.determinant() == 0. {
+            return None;
+        }
+
+        Some(Self {
+            id: state.id?,
+            z: state.z,
+            to_world,
+            to_local: to_world.inverse(),
+            area,
+            clip: state.clip,
+            interactive: state.interactive,
+            target: ShapeTarget::of(state),
+        })
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        if let Some(clip) = self.clip {
+            if !clip.contains(point) {
+                return false;
+            }
+        }
+        self.area.contains(transform::transform_point(&self.to_local, point))
+    }
+
+    /// World space bounds of the visible part of the shape.
+    pub fn bounds(&self) -> Bounds {
+        let bounds = self.area.bounds().transformed(&self.to_world);
+        match self.clip {
+            Some(clip) => bounds.intersection(&clip),
+            None => bounds,
+        }
+    }
+}
+
+/// Shapes with an id drawn in the last frame, in world space.
+#[derive(Debug, Default)]
+pub(crate) struct Picking {
+    pub shapes: Vec<Pickable>,
+}
+
+impl Picking {
+    /// Ids of the shapes containing the point, the topmost first.
+    pub fn hit_test(&self, point: Vec2) -> Vec<ShapeId> {
+        self.ids(|shape| shape.contains(point))
+    }
+
+    /// Ids of the shapes whose bounds overlap the rectangle, the topmost first.
+    pub fn shapes_in_rect(&self, rect: Bounds) -> Vec<ShapeId> {
+        self.ids(|shape| !shape.bounds().intersection(&rect).is_empty())
+    }
+
+    /// Topmost interactive shape under the cursor, with the cursor in the world space of its target.
+    /// `cursor` gives the position of the cursor for a target, `None` if it can not be picked.
+    /// Shapes of a graphics context are rendered after the default camera, so they are on top.
+    pub fn interactive_at(&self, cursor: impl Fn(&ShapeTarget) -> Option<Vec2>) -> Option<(&Pickable, Vec2)> {
+        self.shapes.iter()
+            .filter(|shape| shape.interactive)
+            .filter_map(|shape| {
+                let position = cursor(&shape.target)?;
+                if shape.contains(position) { Some((shape, position)) } else { None }
+            })
+            .max_by(|(a, _), (b, _)| {
+                (a.target.context.is_some(), a.z).partial_cmp(&(b.target.context.is_some(), b.z))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+
+    fn ids(&self, filter: impl Fn(&Pickable) -> bool) -> Vec<ShapeId> {
+        let mut shapes: Vec<&Pickable> = self.shapes.iter().filter(|shape| filter(shape)).collect();
+        shapes.sort_by(|a, b| b.z.partial_cmp(&a.z).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut ids: Vec<ShapeId> = Vec::new();
+        for shape in shapes {
+            if !ids.contains(&shape.id) {
+                ids.push(shape.id);
+            }
+        }
+        ids
+    }
+}
+
+/// Adds `with_id` and `interactive` to the builder of a kind of shape,
+/// operating on the last shape in the given field of `Immediate2DGraphics`.
+macro_rules! impl_shape_id {
+    ($builder:ident, $shapes:ident) => {
+        impl<'a> $builder<'a> {
+            /// Attach an id to the shape, so it is returned by `hit_test` and `shapes_in_rect`.
+            pub fn with_id(self, id: u64) -> Self {
+                let shape = self.graphics.$shapes.last_mut().unwrap();
+                shape.state.id = Some(crate::picking::ShapeId(id));
+                self
+            }
+
+            /// Attach an id to the shape and send hover, click and drag events for it.
+            /// Shapes drawn into a canvas are not picked with the cursor.
+            pub fn interactive(self, id: u64) -> Self {
+                let shape = self.graphics.$shapes.last_mut().unwrap();
+                shape.state.id = Some(crate::picking::ShapeId(id));
+                shape.state.interactive = true;
+                self
+            }
+        }
+    };
+}
+
+/// Collects the shapes with an id submitted this frame, once they are resolved,
+/// to be hit tested until the shapes of the next frame are resolved.
+/// Text is not hit tested.
+pub fn picking_system(
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+    textures: Res<Assets<Texture>>,
+) {
+    let graphics = &mut *immediate_graphics;
+    let resolver = &graphics.resolver;
+    let mut shapes = Vec::new();
+
+    for mut circle in graphics.circles.iter().filter(|circle| circle.state.id.is_some()).cloned() {
+        if !resolver.resolve(&mut circle.state) {
+            continue;
+        }
+        let area = Area::Circle {
+            radius: circle.radius,
+            inner_radius: circle.outline.map(|outline| circle.radius - outline).unwrap_or(0.),
+        };
+        let to_world = circle.state.transform * transform::affine(Vec2::one(), 0., circle.pos);
+        shapes.extend(Pickable::new(&circle.state, to_world, area));
+    }
+
+    for mut rectangle in graphics.rectangles.iter().filter(|rectangle| rectangle.state.id.is_some()).cloned() {
+        if !resolver.resolve(&mut rectangle.state) {
+            continue;
+        }
+        let half_size = rectangle.dimensions / 2.;
+        let area = Area::Rectangle {
+            half_size,
+            inner_half_size: rectangle.outline
+                .map(|outline| half_size - Vec2::splat(outline))
+                .unwrap_or_else(Vec2::zero),
+        };
+        let to_world = rectangle.state.transform * transform::affine(Vec2::one(), rectangle.rotation, rectangle.pos);
+        shapes.extend(Pickable::new(&rectangle.state, to_world, area));
+    }
+
+    for mut line in graphics.lines.iter().filter(|line| line.state.id.is_some()).cloned() {
+        if !resolver.resolve(&mut line.state) {
+            continue;
+        }
+        // the stroke is scaled into world space like when the line is drawn.
+        let line = lines::transform_line(line);
+        let area = Area::Segment {
+            start: line.start,
+            stop: line.stop,
+            radius: line.style.stroke,
+        };
+        shapes.extend(Pickable::new(&line.state, Mat3::identity(), area));
+    }
+
+    for mut image in graphics.images.iter().filter(|image| image.state.id.is_some()).cloned() {
+        if !resolver.resolve(&mut image.state) {
+            continue;
+        }
+        let image = images::prepare_image(image, &textures);
+        let area = Area::Rectangle {
+            half_size: image.size.unwrap() / 2.,
+            inner_half_size: Vec2::zero(),
+        };
+        let to_world = image.state.transform * transform::affine(Vec2::one(), image.rotation, image.pos);
+        shapes.extend(Pickable::new(&image.state, to_world, area));
+    }
+
+    graphics.picking.shapes = shapes;
+}
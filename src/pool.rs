@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use super::bounds::Bounds;
+use super::groups::{Groups, RetainedShape, ShapeGroupHandle};
+use super::resolve::Resolver;
+use super::state::ShapeState;
+use super::stats::Immediate2DGraphicsStats;
+
+/// A kind of shape drawn by one quad per shape, with entities reused across frames.
+pub(crate) trait PooledShape: Clone {
+    fn state(&self) -> &ShapeState;
+    fn state_mut(&mut self) -> &mut ShapeState;
+    /// Area of a resolved shape checked against the view of its camera. `None` keeps the shape.
+    fn view_bounds(&self, textures: &Assets<Texture>) -> Option<Bounds>;
+    /// Turns a resolved shape into the one drawn. `None` if it covers nothing.
+    fn prepare(self, textures: &Assets<Texture>) -> Option<Self>;
+    /// Transformation of the quad drawing a prepared shape.
+    fn quad_transform(&self) -> Mat4;
+}
+
+/// What the update systems of the pooled shapes share.
+pub(crate) struct ShapePool<'a> {
+    pub resolver: &'a Resolver,
+    pub groups: &'a Groups,
+    pub textures: &'a Assets<Texture>,
+    pub stats: &'a mut Immediate2DGraphicsStats,
+}
+
+impl<'a> ShapePool<'a> {
+    /// Resolves the shapes submitted this frame, culls the ones outside the view of their camera
+    /// and draws the rest with the pooled `entities` of their kind, spawning entities once those
+    /// run out. `show` is given `None` for the entities left over, which are hidden.
+    /// Shapes of new retained groups always get entities of their own.
+    pub(crate) fn update<S: PooledShape, E>(
+        &mut self,
+        shapes: &[S],
+        pending: Vec<(ShapeGroupHandle, S)>,
+        entities: impl Iterator<Item = E>,
+        mut show: impl FnMut(E, Option<(S, Transform)>),
+        mut spawn: impl FnMut(S, bool, Transform, Option<RetainedShape>),
+    ) {
+        let resolver = self.resolver;
+        let textures = self.textures;
+        let mut culled = 0;
+
+        let mut shape_iter = shapes.iter().cloned()
+            .filter_map(|mut shape| if resolver.resolve(shape.state_mut()) { Some(shape) } else { None })
+            .filter(|shape| {
+                let in_view = shape.view_bounds(textures)
+                    .map_or(true, |bounds| resolver.is_in_view(shape.state(), bounds));
+                culled += !in_view as usize;
+                in_view
+            })
+            .filter_map(|shape| shape.prepare(textures));
+
+        for entity in entities {
+            if let Some(shape) = shape_iter.next() {
+                self.stats.pooled += 1;
+                self.stats.add_quads(1);
+                let transform = Transform::new(shape.quad_transform());
+                show(entity, Some((shape, transform)));
+            } else {
+                self.stats.hidden += 1;
+                show(entity, None);
+            }
+        }
+
+        for shape in shape_iter {
+            self.stats.spawned += 1;
+            self.stats.add_quads(1);
+            let transform = Transform::new(shape.quad_transform());
+            spawn(shape, true, transform, None);
+        }
+
+        self.stats.culled += culled;
+
+        for (group, mut shape) in pending {
+            if !resolver.resolve(shape.state_mut()) {
+                continue;
+            }
+            let shape = match shape.prepare(textures) {
+                Some(shape) => shape,
+                None => continue,
+            };
+            self.stats.spawned += 1;
+            let retained = RetainedShape { group, base: shape.quad_transform() };
+            let transform = self.groups.transform(&retained);
+            let is_visible = self.groups.is_visible(group);
+            spawn(shape, is_visible, transform, Some(retained));
+        }
+    }
+}
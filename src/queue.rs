@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use super::Immediate2DGraphics;
+
+/// Submission path for systems that should not take `ResMut<Immediate2DGraphics>`.
+/// Systems only need `Res<GraphicsQueue>`, so bevy can run them in parallel.
+/// Every system draws into its own buffer, identified by a key, and the buffers
+/// are merged into `Immediate2DGraphics` in the order of their keys,
+/// so the final drawing order does not depend on how the systems were scheduled.
+#[derive(Debug, Default)]
+pub struct GraphicsQueue {
+    buffers: Mutex<BTreeMap<&'static str, Arc<Mutex<Immediate2DGraphics>>>>,
+}
+
+impl GraphicsQueue {
+    /// Draw into the buffer of `key`. Only calls using the same key wait for each other.
+    /// Retained groups can be created in a buffer, but have to be shown, hidden,
+    /// transformed and removed through `Immediate2DGraphics`.
+    pub fn draw<R>(&self, key: &'static str, draw: impl FnOnce(&mut Immediate2DGraphics) -> R) -> R {
+        let buffer = self.buffers.lock().unwrap()
+            .entry(key)
+            .or_default()
+            .clone();
+
+        let mut buffer = buffer.lock().unwrap();
+        draw(&mut *buffer)
+    }
+
+    /// Moves the shapes of every buffer into the graphics, ordered by key.
+    fn merge_into(&self, graphics: &mut Immediate2DGraphics) {
+        let buffers = self.buffers.lock().unwrap();
+
+        for buffer in buffers.values() {
+            graphics.append(&mut *buffer.lock().unwrap());
+        }
+    }
+}
+
+/// Merges the buffers of the queue before the shapes are resolved.
+/// Shapes submitted to the queue in `GRAPHICS_STAGE` or later are drawn the next frame.
+pub fn queue_merge_system(
+    queue: Res<GraphicsQueue>,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+) {
+    queue.merge_into(&mut *immediate_graphics);
+}
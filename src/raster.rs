@@ -0,0 +1,445 @@
+use bevy::{
+    render::texture::TextureFormat,
+    prelude::*,
+};
+use std::io;
+use std::path::Path;
+use super::Immediate2DGraphics;
+use super::bounds::Bounds;
+use super::circles::{self, CircleStyle};
+use super::rectangles::{self, RectangleStyle};
+use super::lines::{self, LineStyle};
+use super::images::{self, ImageStyle};
+
+/// Image rendered on the cpu by `Immediate2DGraphics::rasterize`.
+#[derive(Debug, Clone)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    /// Rgba pixels in srgb, row by row from the top.
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Encodes the raster as png.
+    pub fn to_png(&self) -> io::Result<Vec<u8>> {
+        let mut png = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+            writer.write_image_data(&self.pixels)
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        }
+        Ok(png)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_png()?)
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn color_to_vec4(color: Color) -> Vec4 {
+    Vec4::new(color.r, color.g, color.b, color.a)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.).min(1.);
+    t * t * (3. - 2. * t)
+}
+
+/// Samples a texture with the nearest texel, like the gpu would with an srgb texture.
+fn sample(texture: &Texture, uv: Vec2) -> Vec4 {
+    let width = texture.size.x() as usize;
+    let height = texture.size.y() as usize;
+    if width == 0 || height == 0 || texture.data.len() < width * height * 4 {
+        return Vec4::zero();
+    }
+
+    let x = ((uv.x() * width as f32) as usize).min(width - 1);
+    let y = ((uv.y() * height as f32) as usize).min(height - 1);
+    let texel = &texture.data[(y * width + x) * 4..][..4];
+
+    let (r, g, b, a) = (texel[0] as f32 / 255., texel[1] as f32 / 255., texel[2] as f32 / 255., texel[3] as f32 / 255.);
+    match texture.format {
+        TextureFormat::Rgba8UnormSrgb => Vec4::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a),
+        TextureFormat::Bgra8UnormSrgb => Vec4::new(srgb_to_linear(b), srgb_to_linear(g), srgb_to_linear(r), a),
+        TextureFormat::Bgra8Unorm => Vec4::new(b, g, r, a),
+        _ => Vec4::new(r, g, b, a),
+    }
+}
+
+fn is_clipped(clip: Vec4, world: Vec2) -> bool {
+    world.x() < clip.x() || world.y() < clip.y() || world.x() > clip.z() || world.y() > clip.w()
+}
+
+/// `fill` of the circle and rectangle shaders.
+fn fill(fill_color: Color, uv_transform: Vec4, uv_offset: Vec4, texture: Option<&Texture>, shape_uv: Vec2) -> Vec4 {
+    let color = color_to_vec4(fill_color);
+    let texture = match texture {
+        Some(texture) => texture,
+        None => return color,
+    };
+
+    let centered = shape_uv - Vec2::splat(0.5);
+    let mut tex_uv = Vec2::new(uv_transform.x(), uv_transform.y()) * centered.x()
+        + Vec2::new(uv_transform.z(), uv_transform.w()) * centered.y()
+        + Vec2::splat(0.5)
+        + Vec2::new(uv_offset.x(), uv_offset.y());
+    if uv_offset.z() > 0.5 {
+        tex_uv = Vec2::new(tex_uv.x() - tex_uv.x().floor(), tex_uv.y() - tex_uv.y().floor());
+    }
+    if tex_uv.x() < 0. || tex_uv.y() < 0. || tex_uv.x() > 1. || tex_uv.y() > 1. {
+        Vec4::zero()
+    } else {
+        color * sample(texture, tex_uv)
+    }
+}
+
+/// `effects` of the circle and rectangle shaders.
+fn effects(shadow_color: Color, glow_color: Color, shadow: Vec4, glow_radius: f32, shadow_dist: f32, dist: f32) -> Vec4 {
+    let blur = shadow.z().max(0.0001);
+    let shadow_alpha = shadow_color.a * (1. - smoothstep(-blur, blur, shadow_dist));
+    let glow_alpha = if glow_radius > 0. {
+        glow_color.a * (1. - smoothstep(0., glow_radius, dist))
+    } else {
+        0.
+    };
+
+    let alpha = glow_alpha + shadow_alpha * (1. - glow_alpha);
+    if alpha <= 0. {
+        return Vec4::zero();
+    }
+
+    let glow_rgb = color_to_vec4(glow_color).truncate();
+    let shadow_rgb = color_to_vec4(shadow_color).truncate();
+    let color = glow_rgb * glow_alpha + shadow_rgb * shadow_alpha * (1. - glow_alpha);
+    (color / alpha).extend(alpha)
+}
+
+fn shade_circle(style: &CircleStyle, texture: Option<&Texture>, quad_uv: Vec2, world: Vec2) -> Option<Vec4> {
+    if is_clipped(style.clip, world) {
+        return None;
+    }
+
+    let pos = (quad_uv - Vec2::splat(0.5)) * 2. * style.extent;
+    let uv = pos / style.radius;
+    let square_len = uv.dot(uv);
+
+    let color = if square_len < 1. {
+        if square_len > 1. - style.border_width {
+            color_to_vec4(style.border_color)
+        } else {
+            fill(style.fill_color, style.uv_transform, style.uv_offset, texture, uv / 2. + Vec2::splat(0.5))
+        }
+    } else {
+        let shadow_offset = Vec2::new(style.shadow.x(), style.shadow.y());
+        effects(
+            style.shadow_color, style.glow_color, style.shadow, style.glow_radius,
+            (pos - shadow_offset).length() - style.radius,
+            pos.length() - style.radius,
+        )
+    };
+
+    if color.w() <= 0. { None } else { Some(color) }
+}
+
+fn box_dist(pos: Vec2, half_size: Vec2) -> f32 {
+    let q = pos.abs() - half_size;
+    q.max(Vec2::zero()).length() + q.x().max(q.y()).min(0.)
+}
+
+fn shade_rectangle(style: &RectangleStyle, texture: Option<&Texture>, quad_uv: Vec2, world: Vec2) -> Option<Vec4> {
+    if is_clipped(style.clip, world) {
+        return None;
+    }
+
+    let half_size = Vec2::new(style.geometry.x(), style.geometry.y());
+    let pos = (quad_uv - Vec2::splat(0.5)) * 2. * Vec2::new(style.geometry.z(), style.geometry.w());
+    let uv = pos / half_size;
+
+    let color = if uv.x().abs() > 1. || uv.y().abs() > 1. {
+        let shadow_offset = Vec2::new(style.shadow.x(), style.shadow.y());
+        effects(
+            style.shadow_color, style.glow_color, style.shadow, style.glow_radius,
+            box_dist(pos - shadow_offset, half_size),
+            box_dist(pos, half_size),
+        )
+    } else if uv.x() < style.border_width.x() - 1. || uv.y() < style.border_width.y() - 1.
+        || uv.x() > 1. - style.border_width.x() || uv.y() > 1. - style.border_width.y() {
+        color_to_vec4(style.border_color)
+    } else {
+        fill(style.fill_color, style.uv_transform, style.uv_offset, texture, uv / 2. + Vec2::splat(0.5))
+    };
+
+    if color.w() <= 0. { None } else { Some(color) }
+}
+
+fn shade_line(style: &LineStyle, quad_uv: Vec2, world: Vec2) -> Option<Vec4> {
+    if is_clipped(style.clip, world) {
+        return None;
+    }
+
+    let pos = Vec2::new(quad_uv.x() * style.width, quad_uv.y() * style.height);
+    let start = Vec2::new(style.stroke, style.height / 2.);
+    let end = Vec2::new(style.width - style.stroke, style.height / 2.);
+
+    let p = if (pos - start).dot(end - start) > 0. {
+        if (pos - end).dot(start - end) > 0. {
+            return Some(color_to_vec4(style.color));
+        }
+        end
+    } else {
+        start
+    };
+
+    let dist = pos - p;
+    if dist.dot(dist) < style.stroke * style.stroke {
+        Some(color_to_vec4(style.color))
+    } else {
+        None
+    }
+}
+
+fn shade_image(style: &ImageStyle, texture: &Texture, quad_uv: Vec2, world: Vec2) -> Option<Vec4> {
+    if is_clipped(style.clip, world) {
+        return None;
+    }
+
+    let min = Vec2::new(style.uv_rect.x(), style.uv_rect.y());
+    let max = Vec2::new(style.uv_rect.z(), style.uv_rect.w());
+    let uv = min + (max - min) * quad_uv;
+
+    let color = color_to_vec4(style.tint) * sample(texture, uv);
+    if color.w() <= 0. { None } else { Some(color) }
+}
+
+/// Which corner of the mesh of a shape has uv `(0, 0)`.
+#[derive(Clone, Copy)]
+enum QuadKind {
+    /// Quad from -0.5 to 0.5 with uvs pointing down, like `QUAD_HANDLE`.
+    Centered,
+    /// Quad from 0 to 1 with uvs equal to the positions, like the mesh of lines.
+    Unit,
+}
+
+/// A prepared shape waiting to be rasterized.
+struct RasterShape<'a> {
+    z: f32,
+    transform: Mat4,
+    quad: QuadKind,
+    shade: Box<dyn Fn(Vec2, Vec2) -> Option<Vec4> + 'a>,
+}
+
+/// Rasterizes prepared shapes into a linear color buffer, blending them like the shape pipelines.
+struct Rasterizer {
+    viewport: Bounds,
+    width: u32,
+    height: u32,
+    colors: Vec<Vec4>,
+}
+
+impl Rasterizer {
+    fn pixel_to_world(&self, x: f32, y: f32) -> Vec2 {
+        let size = self.viewport.size();
+        Vec2::new(
+            self.viewport.min.x() + x / self.width as f32 * size.x(),
+            self.viewport.max.y() - y / self.height as f32 * size.y(),
+        )
+    }
+
+    fn world_to_pixel(&self, world: Vec2) -> Vec2 {
+        let size = self.viewport.size();
+        Vec2::new(
+            (world.x() - self.viewport.min.x()) / size.x() * self.width as f32,
+            (self.viewport.max.y() - world.y()) / size.y() * self.height as f32,
+        )
+    }
+
+    fn draw(&mut self, shape: &RasterShape) {
+        let transform = Mat3::from_cols(
+            Vec3::new(shape.transform.x_axis().x(), shape.transform.x_axis().y(), 0.),
+            Vec3::new(shape.transform.y_axis().x(), shape.transform.y_axis().y(), 0.),
+            Vec3::new(shape.transform.w_axis().x(), shape.transform.w_axis().y(), 1.),
+        );
+        // only quads without an area are skipped, tiny ones under a small scale still cover pixels.
+        let determinant = transform.determinant();
+        if determinant == 0. || !determinant.is_finite() {
+            return;
+        }
+        let inverse = transform.inverse();
+
+        let corners = match shape.quad {
+            QuadKind::Centered => [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)],
+            QuadKind::Unit => [(0., 0.), (1., 0.), (1., 1.), (0., 1.)],
+        };
+        let mut min = Vec2::splat(f32::MAX);
+        let mut max = Vec2::splat(f32::MIN);
+        for (x, y) in corners.iter() {
+            let world = (transform * Vec3::new(*x, *y, 1.)).truncate();
+            let pixel = self.world_to_pixel(world);
+            min = min.min(pixel);
+            max = max.max(pixel);
+        }
+
+        let start_x = min.x().floor().max(0.) as u32;
+        let start_y = min.y().floor().max(0.) as u32;
+        let end_x = (max.x().ceil().max(0.) as u32).min(self.width);
+        let end_y = (max.y().ceil().max(0.) as u32).min(self.height);
+
+        for y in start_y..end_y {
+            for x in start_x..end_x {
+                let world = self.pixel_to_world(x as f32 + 0.5, y as f32 + 0.5);
+                let local = (inverse * world.extend(1.)).truncate();
+
+                let quad_uv = match shape.quad {
+                    QuadKind::Centered => {
+                        if local.x().abs() > 0.5 || local.y().abs() > 0.5 {
+                            continue;
+                        }
+                        Vec2::new(local.x() + 0.5, 0.5 - local.y())
+                    }
+                    QuadKind::Unit => {
+                        if local.x() < 0. || local.y() < 0. || local.x() > 1. || local.y() > 1. {
+                            continue;
+                        }
+                        local
+                    }
+                };
+
+                if let Some(color) = (shape.shade)(quad_uv, world) {
+                    let index = (y * self.width + x) as usize;
+                    let destination = self.colors[index];
+                    let alpha = color.w().max(0.).min(1.);
+                    let rgb = color.truncate() * alpha + destination.truncate() * (1. - alpha);
+                    self.colors[index] = rgb.extend((alpha + destination.w()).min(1.));
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Raster {
+        let pixels = self.colors.iter()
+            .flat_map(|color| {
+                let to_byte = |value: f32| (value.max(0.).min(1.) * 255.).round() as u8;
+                vec![
+                    to_byte(linear_to_srgb(color.x())),
+                    to_byte(linear_to_srgb(color.y())),
+                    to_byte(linear_to_srgb(color.z())),
+                    to_byte(color.w()),
+                ]
+            })
+            .collect();
+
+        Raster {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+/// Renders the submitted circles, rectangles, lines and images inside `viewport`.
+pub(crate) fn rasterize(
+    graphics: &Immediate2DGraphics,
+    viewport: Bounds,
+    width: u32,
+    height: u32,
+    textures: &Assets<Texture>,
+) -> Raster {
+    let resolver = &graphics.resolver;
+    let mut shapes: Vec<RasterShape> = Vec::new();
+
+    for mut circle in graphics.circles.iter().cloned() {
+        if !resolver.resolve(&mut circle.state) {
+            continue;
+        }
+        let circle = circles::prepare_circle(circle, textures);
+        let texture = circle.texture.as_ref().and_then(|texture| textures.get(texture));
+        shapes.push(RasterShape {
+            z: circle.state.z,
+            transform: circles::calc_transform_for_circle(&circle),
+            quad: QuadKind::Centered,
+            shade: Box::new(move |uv, world| shade_circle(&circle.style, texture, uv, world)),
+        });
+    }
+
+    for mut rectangle in graphics.rectangles.iter().cloned() {
+        if !resolver.resolve(&mut rectangle.state) {
+            continue;
+        }
+        let rectangle = rectangles::prepare_rectangle(rectangle, textures);
+        let texture = rectangle.texture.as_ref().and_then(|texture| textures.get(texture));
+        shapes.push(RasterShape {
+            z: rectangle.state.z,
+            transform: rectangles::calc_transform_for_rectangle(&rectangle),
+            quad: QuadKind::Centered,
+            shade: Box::new(move |uv, world| shade_rectangle(&rectangle.style, texture, uv, world)),
+        });
+    }
+
+    for mut line in graphics.lines.iter().cloned() {
+        if !resolver.resolve(&mut line.state) {
+            continue;
+        }
+        let line = lines::transform_line(line);
+        if line.is_degenerate() {
+            continue;
+        }
+        shapes.push(RasterShape {
+            z: line.state.z,
+            transform: lines::calc_transform_for_line(line.start, line.stop, line.style.stroke, line.state.z),
+            quad: QuadKind::Unit,
+            shade: Box::new(move |uv, world| shade_line(&line.style, uv, world)),
+        });
+    }
+
+    for mut image in graphics.images.iter().cloned() {
+        if !resolver.resolve(&mut image.state) {
+            continue;
+        }
+        let image = images::prepare_image(image, textures);
+        let texture = match textures.get(&image.texture) {
+            Some(texture) => texture,
+            None => continue,
+        };
+        shapes.push(RasterShape {
+            z: image.state.z,
+            transform: images::calc_transform_for_image(&image),
+            quad: QuadKind::Centered,
+            shade: Box::new(move |uv, world| shade_image(&image.style, texture, uv, world)),
+        });
+    }
+
+    // drawn back to front, like the transparent draws of the main pass.
+    shapes.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut rasterizer = Rasterizer {
+        viewport,
+        width,
+        height,
+        colors: vec![Vec4::zero(); (width * height) as usize],
+    };
+    for shape in shapes.iter() {
+        rasterizer.draw(shape);
+    }
+    rasterizer.finish()
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use super::Immediate2DGraphics;
+use super::circles::Circle;
+use super::rectangles::Rectangle;
+use super::lines::Line;
+use super::images::Image;
+use super::labels::Label;
+use super::resolve::Resolver;
+use super::state::ShapeState;
+
+/// Shapes drawn in one frame, resolved into world space.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrameCommands {
+    circles: Vec<Circle>,
+    rectangles: Vec<Rectangle>,
+    lines: Vec<Line>,
+    images: Vec<Image>,
+    labels: Vec<Label>,
+    /// Number of shapes submitted in the frame, used to order shapes drawn on top.
+    depth: u32,
+}
+
+/// Clones the shapes that can be resolved, detached from entities and cameras.
+/// Shapes with a lifetime are recorded every frame they are drawn, so each copy only lasts one.
+fn detached<T: Clone>(shapes: &[T], resolver: &Resolver, state: fn(&mut T) -> &mut ShapeState) -> Vec<T> {
+    shapes.iter()
+        .cloned()
+        .filter_map(|mut shape| {
+            let state = state(&mut shape);
+            if resolver.detach(state) {
+                state.lifetime = state.lifetime.map(|lifetime| lifetime.frozen());
+                Some(shape)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+impl FrameCommands {
+    /// Captures the shapes submitted so far, once they are resolved.
+    /// Retained groups are not captured.
+    pub(crate) fn capture(graphics: &Immediate2DGraphics) -> Self {
+        let resolver = &graphics.resolver;
+        Self {
+            circles: detached(&graphics.circles, resolver, |circle| &mut circle.state),
+            rectangles: detached(&graphics.rectangles, resolver, |rectangle| &mut rectangle.state),
+            lines: detached(&graphics.lines, resolver, |line| &mut line.state),
+            images: detached(&graphics.images, resolver, |image| &mut image.state),
+            labels: detached(&graphics.labels, resolver, |label| &mut label.state),
+            depth: graphics.depth,
+        }
+    }
+
+    /// Graphics submitting the shapes of the frame, to be appended to others.
+    pub(crate) fn to_graphics(&self) -> Immediate2DGraphics {
+        Immediate2DGraphics {
+            circles: self.circles.clone(),
+            rectangles: self.rectangles.clone(),
+            lines: self.lines.clone(),
+            images: self.images.clone(),
+            labels: self.labels.clone(),
+            depth: self.depth,
+            ..Default::default()
+        }
+    }
+}
+
+/// Writes the shapes of every frame to a file, one line of json per frame.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    writer: Option<BufWriter<File>>,
+    frames: usize,
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    /// Start recording into a file, replacing it if it exists.
+    pub fn start(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.writer = Some(BufWriter::new(File::create(path)?));
+        self.frames = 0;
+        Ok(())
+    }
+
+    /// Stop recording and flush the file.
+    pub fn stop(&mut self) -> io::Result<()> {
+        match self.writer.take() {
+            Some(mut writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Number of frames recorded since the recording was started.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Error of the frame that could not be written, which stopped the recording.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+/// Plays back frames written by a `Recorder`, drawing them on top of the shapes of the current frame.
+/// The shapes are drawn in world space of the default context, where they were when recorded.
+#[derive(Debug, Default)]
+pub struct Replayer {
+    frames: Vec<FrameCommands>,
+    /// Frame drawn next.
+    position: usize,
+    playing: bool,
+}
+
+impl Replayer {
+    /// Load a recording and start playing it from the first frame.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut frames = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let frame = serde_json::from_str(&line)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            frames.push(frame);
+        }
+
+        self.frames = frames;
+        self.position = 0;
+        self.playing = true;
+        Ok(())
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Keep drawing the current frame.
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Pause and advance to the next frame.
+    pub fn step(&mut self) {
+        self.playing = false;
+        if self.position + 1 < self.frames.len() {
+            self.position += 1;
+        }
+    }
+
+    /// Continue from the given frame, or stop drawing if it is past the end of the recording.
+    pub fn seek(&mut self, frame: usize) {
+        self.position = frame.min(self.frames.len());
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Index of the frame drawn next.
+    pub fn frame(&self) -> usize {
+        self.position
+    }
+
+    /// Number of frames in the recording.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// Appends the current frame of the replay to the submitted shapes and advances it while playing.
+pub fn replay_system(
+    mut replayer: ResMut<Replayer>,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+) {
+    let replayer = &mut *replayer;
+    match replayer.frames.get(replayer.position) {
+        Some(frame) => {
+            immediate_graphics.append(&mut frame.to_graphics());
+            if replayer.playing {
+                replayer.position += 1;
+            }
+        }
+        None => replayer.playing = false,
+    }
+}
+
+/// Writes the submitted shapes to the recording, once they are resolved.
+pub fn record_system(
+    mut recorder: ResMut<Recorder>,
+    immediate_graphics: Res<Immediate2DGraphics>,
+) {
+    let writer = match &mut recorder.writer {
+        Some(writer) => writer,
+        None => return,
+    };
+
+    let frame = FrameCommands::capture(&immediate_graphics);
+    let result = serde_json::to_writer(&mut *writer, &frame)
+        .map_err(io::Error::from)
+        .and_then(|_| writer.write_all(b"\n"));
+
+    match result {
+        Ok(()) => recorder.frames += 1,
+        Err(error) => {
+            recorder.writer = None;
+            recorder.error = Some(error);
+        }
+    }
+}
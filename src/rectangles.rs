@@ -9,20 +9,45 @@ use bevy::{
         render_graph::{RenderGraph, base, RenderResourcesNode}
     },
     core::Byteable,
+    ecs::Without,
     sprite::QUAD_HANDLE,
     prelude::*,
 };
 use std::ops::{DerefMut, Deref};
+use std::time::Instant;
 use super::Immediate2DGraphics;
+use super::stats::Immediate2DGraphicsStats;
+use super::state::ShapeState;
+use super::textures::{self, ShapeTexture, TextureFit, UvTransform};
+use super::effects::{Effects, Shadow, Glow};
+use super::transform;
+use super::bounds;
+use super::lifetime;
+use super::canvas::CanvasPass;
+use super::contexts::ShapeTarget;
+use super::groups::RetainedShape;
+use super::pool::{PooledShape, ShapePool};
 use base::MainPass;
 
-#[derive(Debug, RenderResources, RenderResource)]
+#[derive(Debug, Clone, RenderResources, RenderResource)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[render_resources(from_self)]
 #[allow(unused)]
+#[repr(C)]
 pub struct RectangleStyle {
     pub fill_color: Color,
     pub border_color: Color,
+    pub uv_transform: Vec4,
+    pub uv_offset: Vec4,
+    pub shadow_color: Color,
+    pub glow_color: Color,
+    pub shadow: Vec4,
+    /// Half the size of the rectangle and half the size of the quad,
+    /// which is larger if there is a shadow or glow.
+    pub geometry: Vec4,
+    pub clip: Vec4,
     pub border_width: Vec2,
+    pub glow_radius: f32,
 }
 unsafe impl Byteable for RectangleStyle {}
 
@@ -31,28 +56,71 @@ impl Default for RectangleStyle {
         Self {
             fill_color: Color::BLACK,
             border_color: Color::BLACK,
+            uv_transform: Vec4::new(1., 0., 0., 1.),
+            uv_offset: Vec4::zero(),
+            shadow_color: Color::rgba(0., 0., 0., 0.),
+            glow_color: Color::rgba(0., 0., 0., 0.),
+            shadow: Vec4::zero(),
+            geometry: Vec4::one(),
+            clip: bounds::clip_uniform(&None),
             border_width: Vec2::zero(),
+            glow_radius: 0.,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rectangle {
+    pub state: ShapeState,
     pub style: RectangleStyle,
     pub pos: Vec2,
     pub dimensions: Vec2,
     pub rotation: f32,
+    pub texture: Option<Handle<Texture>>,
+    pub uv: UvTransform,
+    pub effects: Effects,
+    /// Width of the outline in world units, if only the outline is drawn.
+    pub outline: Option<f32>,
+    /// Whether the fill color was set. Textures are drawn untinted otherwise.
+    pub has_color: bool,
 }
 
 impl Rectangle {
-    pub fn new(x: f32, y: f32) -> Self {
+    pub fn new(state: ShapeState, x: f32, y: f32) -> Self {
         Self {
+            state,
             style: RectangleStyle::default(),
             pos: Vec2::new(x, y),
             dimensions: Vec2::new(100., 100.),
-            rotation: 0.
+            rotation: 0.,
+            texture: None,
+            uv: UvTransform::default(),
+            effects: Effects::default(),
+            outline: None,
+            has_color: false,
         }
     }
+
+    pub fn new_outline(state: ShapeState, x: f32, y: f32) -> Self {
+        let mut rectangle = Self::new(state, x, y);
+        rectangle.style.fill_color = Color::rgba(0., 0., 0., 0.);
+        rectangle.outline = Some(1.);
+        rectangle
+    }
+
+    /// Bounds of the rotated rectangle in the space it is transformed into, usually world units,
+    /// limited to its clip rectangle. Shadows and glows are not included.
+    pub fn bounds(&self) -> bounds::Bounds {
+        let rectangle = bounds::Bounds::from_center_size(Vec2::zero(), self.dimensions);
+        self.state.clipped(rectangle.transformed(&self.local_affine()))
+    }
+
+    /// Maps the rotated space of the rectangle, centered on it but not scaled by its size,
+    /// to world units.
+    pub(crate) fn local_affine(&self) -> Mat3 {
+        self.state.transform * transform::affine(Vec2::one(), self.rotation, self.pos)
+    }
 }
 
 pub struct RectangleBuilder<'a> {
@@ -64,6 +132,7 @@ impl<'a> RectangleBuilder<'a> {
     pub fn with_color(self, color: Color) -> Self {
         let rectangle = self.graphics.rectangles.last_mut().unwrap();
         rectangle.style.fill_color = color;
+        rectangle.has_color = true;
         self
     }
 
@@ -94,6 +163,51 @@ impl<'a> RectangleBuilder<'a> {
         rectangles.dimensions.set_y(height);
         self
     }
+
+    /// Fill rectangle with a texture.
+    /// The texture is tinted with the color of the rectangle, if one is set before or after.
+    pub fn with_texture(self, texture: Handle<Texture>) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.texture = Some(texture);
+        self
+    }
+
+    /// Define how the texture is mapped onto the rectangle.
+    pub fn with_texture_fit(self, fit: TextureFit) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.uv.fit = fit;
+        self
+    }
+
+    /// Offset texture in texture coordinates.
+    pub fn with_texture_offset(self, x: f32, y: f32) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.uv.offset = Vec2::new(x, y);
+        self
+    }
+
+    /// Rotate texture around the center of the rectangle.
+    pub fn with_texture_rotation(self, rotation: f32) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.uv.rotation = rotation;
+        self
+    }
+
+    /// Add a shadow below the rectangle. The offset is in world units, so the shadow keeps its
+    /// direction and distance when the rectangle is rotated, scaled or attached to an entity.
+    /// The width of the soft edge is scaled with the rectangle.
+    pub fn with_shadow(self, offset: Vec2, blur: f32, color: Color) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.effects.shadow = Some(Shadow { offset, blur, color });
+        self
+    }
+
+    /// Add a glow fading out over `radius` world units around the rectangle, scaled with it.
+    pub fn with_glow(self, radius: f32, color: Color) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.effects.glow = Some(Glow { radius, color });
+        self
+    }
 }
 
 impl<'a> Drop for RectangleBuilder<'a> {
@@ -114,6 +228,62 @@ impl<'a> Drop for RectangleBuilder<'a> {
     }
 }
 
+pub struct RectangleOutlineBuilder<'a> {
+    pub(crate) graphics: &'a mut Immediate2DGraphics,
+}
+
+impl<'a> RectangleOutlineBuilder<'a> {
+    /// Define color of the outline.
+    pub fn with_color(self, color: Color) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.style.border_color = color;
+        self
+    }
+
+    /// Define width of the outline in world units.
+    pub fn with_stroke(self, stroke: f32) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.outline = Some(stroke);
+        self
+    }
+
+    pub fn with_rotation(self, rotation: f32) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.rotation = rotation;
+        self
+    }
+
+    pub fn with_width(self, width: f32) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.dimensions.set_x(width);
+        self
+    }
+
+    pub fn with_height(self, height: f32) -> Self {
+        let rectangle = self.graphics.rectangles.last_mut().unwrap();
+        rectangle.dimensions.set_y(height);
+        self
+    }
+}
+
+impl_lifetime!(RectangleBuilder, rectangles);
+impl_lifetime!(RectangleOutlineBuilder, rectangles);
+impl_shape_id!(RectangleBuilder, rectangles);
+impl_shape_id!(RectangleOutlineBuilder, rectangles);
+
+impl<'a> Deref for RectangleOutlineBuilder<'a> {
+    type Target = Immediate2DGraphics;
+    fn deref(&self) -> &Self::Target {
+        &*self.graphics
+    }
+}
+
+impl<'a> DerefMut for RectangleOutlineBuilder<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.graphics
+    }
+}
+
 impl<'a> Deref for RectangleBuilder<'a> {
     type Target = Immediate2DGraphics;
     fn deref(&self) -> &Self::Target {
@@ -129,7 +299,7 @@ impl<'a> DerefMut for RectangleBuilder<'a> {
 
 const RECTANGLE_PIPELINE: Handle<PipelineDescriptor> = Handle::from_u128(309579415678454561098776985274718569653);
 
-const RECTANGLE_STYLE_NODE: &str = "RectangleStyle";
+pub(crate) const RECTANGLE_STYLE_NODE: &str = "RectangleStyle";
 
 pub fn add_render_graph(resources: &Resources) {
         let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
@@ -148,7 +318,7 @@ pub fn add_render_graph(resources: &Resources) {
         let fragment_shader_handle = shaders.add(
             Shader::from_glsl(ShaderStage::Fragment, include_str!("shader/rectangle.frag"))
         );
-        let pipeline_descriptor = PipelineDescriptor::default_config(ShaderStages {
+        let pipeline_descriptor = textures::shape_pipeline(ShaderStages {
             vertex: vertex_shader_handle,
             fragment: Some(fragment_shader_handle),
         });
@@ -163,24 +333,55 @@ struct RectangleComponents {
     pub draw: Draw,
     pub render_pipelines: RenderPipelines,
     pub main_pass: MainPass,
+    pub canvas_pass: CanvasPass,
+    pub target: ShapeTarget,
     pub transform: Transform,
-    pub translation: Translation,
-    pub rotation: Rotation,
-    pub scale: NonUniformScale,
+}
+
+pub(crate) fn calc_transform_for_rectangle(rectangle: &Rectangle) -> Mat4 {
+    let size = Vec2::new(rectangle.style.geometry.z() * 2., rectangle.style.geometry.w() * 2.);
+    let local = transform::affine(size, rectangle.rotation, rectangle.pos);
+    transform::affine_to_mat4(&(rectangle.state.transform * local), rectangle.state.z)
+}
+
+impl PooledShape for Rectangle {
+    fn state(&self) -> &ShapeState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut ShapeState {
+        &mut self.state
+    }
+
+    fn view_bounds(&self, _textures: &Assets<Texture>) -> Option<bounds::Bounds> {
+        let margin = self.effects.margin(&self.local_affine()) * transform::scale_factor(&self.state.transform);
+        Some(self.bounds().expanded(margin))
+    }
+
+    fn prepare(self, textures: &Assets<Texture>) -> Option<Self> {
+        Some(prepare_rectangle(self, textures))
+    }
+
+    fn quad_transform(&self) -> Mat4 {
+        calc_transform_for_rectangle(self)
+    }
 }
 
 pub fn rectangle_update_system(
     mut commands: Commands,
     mut immediate_graphics: ResMut<Immediate2DGraphics>,
-    mut query: Query<(
+    mut stats: ResMut<Immediate2DGraphicsStats>,
+    textures: Res<Assets<Texture>>,
+    mut query: Query<Without<RetainedShape, (
         &mut Draw,
         &mut RectangleStyle,
-        &mut Translation,
-        &mut NonUniformScale,
-        &mut Rotation,
-    )>,
+        &mut ShapeTexture,
+        &mut RenderPipelines,
+        &mut Transform,
+        &mut ShapeTarget,
+    )>>,
 ) {
-    commands.spawn(Camera2dComponents::default());
+    let start = Instant::now();
 
     let rectangle_render_pipelines = || // Workaround because RenderPipelines is not Clone.
         RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
@@ -202,39 +403,111 @@ pub fn rectangle_update_system(
         ]);
 
     let mut query_borrow = query.iter();
-    let mut query_iter = query_borrow.iter();
 
-    let mut rectangle_iter = immediate_graphics.rectangles.drain(..);
+    let graphics = &mut *immediate_graphics;
+    let pending = graphics.groups.take_pending(|shapes| &mut shapes.rectangles);
+    let mut pool = ShapePool {
+        resolver: &graphics.resolver,
+        groups: &graphics.groups,
+        textures: &textures,
+        stats: &mut stats,
+    };
+
+    pool.update(
+        &graphics.rectangles,
+        pending,
+        query_borrow.iter(),
+        |(mut draw, mut style, mut texture, mut pipelines, mut transform, mut target), rectangle| {
+            draw.is_visible = rectangle.is_some();
+            if let Some((rectangle, quad)) = rectangle {
+                textures::set_texture_shader_def(&mut pipelines, rectangle.texture.is_some());
+                texture.texture = rectangle.texture;
+                *transform = quad;
+                *target = ShapeTarget::of(&rectangle.state);
+                *style = rectangle.style;
+            }
+        },
+        |rectangle, is_visible, transform, retained| {
+            let entity = spawn_rectangle(&mut commands, rectangle, rectangle_render_pipelines(), is_visible, transform);
+            if let Some(retained) = retained {
+                entity.with(retained);
+            }
+        },
+    );
 
-    for (mut draw, mut style, mut trans, mut scale, mut rot) in &mut query_iter {
-        let rectangle = if let Some(rectangle) = rectangle_iter.next() {
-            rectangle
-        } else {
-            draw.is_visible = false;
-            break;
-        };
+    stats.submitted.rectangles = graphics.rectangles.len();
+
+    stats.update_time += start.elapsed();
+}
 
-        draw.is_visible = true;
-        *style = rectangle.style;
-        *scale = NonUniformScale::new(rectangle.dimensions.x(), rectangle.dimensions.y(), 0.);
-        *trans = Translation::from(rectangle.pos.extend(0.));
-        *rot = Rotation::from_rotation_z(rectangle.rotation);
+fn spawn_rectangle<'a>(
+    commands: &'a mut Commands,
+    rectangle: Rectangle,
+    mut render_pipelines: RenderPipelines,
+    is_visible: bool,
+    transform: Transform,
+) -> &'a mut Commands {
+    textures::set_texture_shader_def(&mut render_pipelines, rectangle.texture.is_some());
+
+    commands.spawn(RectangleComponents {
+        mesh: QUAD_HANDLE,
+        draw: Draw {
+            is_visible,
+            is_transparent: true,
+            ..Default::default()
+        },
+        render_pipelines,
+        transform,
+        target: ShapeTarget::of(&rectangle.state),
+        ..Default::default()
+    })
+    .with(rectangle.style)
+    .with(ShapeTexture { texture: rectangle.texture })
+}
+
+/// Writes the geometry, uv transform, effects, clipping and fading of a rectangle into its style.
+pub(crate) fn prepare_rectangle(mut rectangle: Rectangle, textures: &Assets<Texture>) -> Rectangle {
+    rectangle.style.clip = bounds::clip_uniform(&rectangle.state.clip);
+
+    // the shader multiplies the texture with the fill color, white unless one was set.
+    if rectangle.texture.is_some() && !rectangle.has_color {
+        rectangle.style.fill_color = Color::WHITE;
     }
 
-    for (mut draw, _, _, _, _) in query_iter {
-        draw.is_visible = false;
+    let opacity = rectangle.state.opacity();
+    rectangle.style.fill_color = lifetime::fade(rectangle.style.fill_color, opacity);
+    rectangle.style.border_color = lifetime::fade(rectangle.style.border_color, opacity);
+
+    let affine = rectangle.local_affine();
+    let effects = rectangle.effects.uniforms(&affine);
+    rectangle.style.shadow_color = lifetime::fade(effects.shadow_color, opacity);
+    rectangle.style.glow_color = lifetime::fade(effects.glow_color, opacity);
+    rectangle.style.shadow = effects.shadow;
+    rectangle.style.glow_radius = effects.glow_radius;
+
+    let half_size = rectangle.dimensions / 2.;
+    let margin = rectangle.effects.margin(&affine);
+    rectangle.style.geometry = Vec4::new(
+        half_size.x(),
+        half_size.y(),
+        half_size.x() + margin,
+        half_size.y() + margin,
+    );
+
+    // the shader measures the border relative to half the size on each axis.
+    if let Some(stroke) = rectangle.outline {
+        rectangle.style.border_width = Vec2::new(
+            f32::min(stroke / half_size.x(), 1.),
+            f32::min(stroke / half_size.y(), 1.),
+        );
     }
 
-    for rectangle in rectangle_iter {
-        commands.spawn(RectangleComponents {
-            mesh: QUAD_HANDLE,
-            render_pipelines: rectangle_render_pipelines(),
-            scale: NonUniformScale::new(rectangle.dimensions.x(), rectangle.dimensions.y(), 0.),
-            translation: Translation::from(rectangle.pos.extend(0.)),
-            rotation: Rotation::from_rotation_z(rectangle.rotation),
-            ..Default::default()
-        })
-        .with(rectangle.style);
+    if let Some(texture) = &rectangle.texture {
+        let texture_size = textures.get(texture).map(|texture| texture.size);
+        let (uv_transform, uv_offset) = rectangle.uv.uniforms(rectangle.dimensions, texture_size);
+        rectangle.style.uv_transform = uv_transform;
+        rectangle.style.uv_offset = uv_offset;
     }
+    rectangle
 }
 
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+use bincode::Options;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use super::Immediate2DGraphics;
+use super::record::FrameCommands;
+
+/// Number of encoded frames waiting to be sent before new frames are dropped.
+const FRAME_BUFFER: usize = 4;
+
+/// Largest encoded frame that is sent or received, in bytes.
+/// Larger frames are not sent, and a source receiving one closes the connection.
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+fn bincode_options() -> impl Options {
+    bincode::options().with_limit(MAX_FRAME_SIZE as u64)
+}
+
+impl FrameCommands {
+    /// Encodes the frame into a compact binary representation.
+    /// Fails if it is larger than `MAX_FRAME_SIZE`.
+    pub fn encode(&self) -> io::Result<Vec<u8>> {
+        bincode_options().serialize(self).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Decodes a frame written by `encode`.
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        bincode_options().deserialize(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// Writes an encoded frame prefixed by its length as a little endian u32.
+fn write_frame(mut stream: &TcpStream, frame: &[u8]) -> io::Result<()> {
+    stream.write_all(&(frame.len() as u32).to_le_bytes())?;
+    stream.write_all(frame)
+}
+
+/// Reads a frame written by `write_frame`, without allocating more than `MAX_FRAME_SIZE`
+/// for a length announced by the other side.
+fn read_frame(stream: &mut TcpStream) -> io::Result<FrameCommands> {
+    let mut length = [0; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is larger than {} bytes", length, MAX_FRAME_SIZE),
+        ));
+    }
+
+    let mut frame = vec![0; length];
+    stream.read_exact(&mut frame)?;
+    FrameCommands::decode(&frame)
+}
+
+/// Streams the shapes of every frame to viewers connected over tcp,
+/// so apps without a window can publish their debug drawing.
+/// Frames are sent from a background thread and dropped while the viewers fall behind.
+/// The frames are not encrypted or authenticated, so listen on a local address like
+/// `127.0.0.1:7878` unless the network is trusted.
+#[derive(Debug, Default)]
+pub struct RemoteSink {
+    sender: Option<Mutex<SyncSender<Vec<u8>>>>,
+    error: Option<io::Error>,
+}
+
+impl RemoteSink {
+    /// Start accepting viewers on the given address and return the address bound to.
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let local_address = listener.local_addr()?;
+
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(FRAME_BUFFER);
+        thread::spawn(move || {
+            let mut viewers = Vec::new();
+            // ends once the sink stops and drops the sender.
+            for frame in receiver {
+                while let Ok((viewer, _)) = listener.accept() {
+                    if viewer.set_nonblocking(false).is_ok() {
+                        viewer.set_nodelay(true).ok();
+                        viewers.push(viewer);
+                    }
+                }
+                viewers.retain(|viewer| write_frame(viewer, &frame).is_ok());
+            }
+        });
+
+        self.sender = Some(Mutex::new(sender));
+        Ok(local_address)
+    }
+
+    /// Disconnect all viewers and stop accepting new ones.
+    pub fn stop(&mut self) {
+        self.sender = None;
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Error of the last frame that could not be sent, because it was too large.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+/// Receives the frames of a `RemoteSink` and draws the latest one every frame,
+/// on top of the shapes of the current frame.
+#[derive(Debug, Default)]
+pub struct RemoteSource {
+    latest: Arc<Mutex<Option<FrameCommands>>>,
+    /// Error that closed the last connection, set by its background thread.
+    error: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl RemoteSource {
+    /// Connect to a sink. Its frames are drawn until the connection is closed.
+    pub fn connect(&mut self, address: impl ToSocketAddrs) -> io::Result<()> {
+        let mut stream = TcpStream::connect(address)?;
+
+        // a new latest frame, so a previous connection stops updating this source.
+        let latest = Arc::new(Mutex::new(None));
+        self.latest = latest.clone();
+        let error = Arc::new(Mutex::new(None));
+        self.error = error.clone();
+
+        thread::spawn(move || {
+            loop {
+                match read_frame(&mut stream) {
+                    Ok(frame) => *latest.lock().unwrap() = Some(frame),
+                    Err(read_error) => {
+                        // the sink closing the connection is not an error.
+                        if read_error.kind() != io::ErrorKind::UnexpectedEof {
+                            *error.lock().unwrap() = Some(read_error);
+                        }
+                        *latest.lock().unwrap() = None;
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Error that closed the connection, if it was not closed by the sink.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// Whether a frame was received and the connection is still open.
+    pub fn is_receiving(&self) -> bool {
+        self.latest.lock().unwrap().is_some()
+    }
+}
+
+/// Sends the submitted shapes to the viewers of the sink, once they are resolved.
+pub fn remote_sink_system(
+    mut sink: ResMut<RemoteSink>,
+    immediate_graphics: Res<Immediate2DGraphics>,
+) {
+    if !sink.is_listening() {
+        return;
+    }
+
+    let frame = match FrameCommands::capture(&immediate_graphics).encode() {
+        Ok(frame) => frame,
+        Err(error) => {
+            sink.error = Some(error);
+            return;
+        }
+    };
+    let sender = sink.sender.as_ref().unwrap();
+    let disconnected = matches!(sender.lock().unwrap().try_send(frame), Err(TrySendError::Disconnected(_)));
+    if disconnected {
+        sink.sender = None;
+    }
+}
+
+/// Appends the latest frame received by the source to the submitted shapes.
+pub fn remote_source_system(
+    source: Res<RemoteSource>,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+) {
+    if let Some(frame) = &*source.latest.lock().unwrap() {
+        immediate_graphics.append(&mut frame.to_graphics());
+    }
+}
@@ -0,0 +1,192 @@
+use bevy::{
+    render::{
+        camera::{Camera, OrthographicProjection},
+        render_graph::base,
+    },
+    prelude::*,
+};
+use std::collections::HashMap;
+use super::Immediate2DGraphics;
+use super::scope::ScopedGraphics;
+use super::state::ShapeState;
+use super::bounds::Bounds;
+use super::canvas::CanvasCamera;
+use super::contexts::GraphicsContext;
+use super::space::{CoordinateSpace, Viewport};
+
+/// Canvas and context of a shape, identifying the camera rendering it.
+type TargetKey = (Option<Handle<Texture>>, Option<&'static str>);
+
+/// Resolves the state of submitted shapes into world space, at the time they are rendered.
+#[derive(Debug, Default)]
+pub(crate) struct Resolver {
+    /// Transformation of every entity shapes are attached to, `None` if the entity is gone.
+    entities: HashMap<Entity, Option<Mat3>>,
+    /// Viewport of the camera rendering every target.
+    viewports: HashMap<TargetKey, Viewport>,
+}
+
+impl Resolver {
+    /// Replaces the transformation of the state with its world space transformation.
+    /// Returns false if the shape is attached to an entity that does not exist anymore,
+    /// or is drawn in screen space but no camera renders its target.
+    pub(crate) fn resolve(&self, state: &mut ShapeState) -> bool {
+        if let Some(entity) = state.attached_to {
+            match self.entities.get(&entity) {
+                Some(Some(entity_transform)) => {
+                    state.transform = *entity_transform * state.transform;
+                    state.clip = state.clip.map(|clip| clip.transformed(entity_transform));
+                }
+                _ => return false,
+            }
+        }
+
+        if state.space != CoordinateSpace::World {
+            match self.viewports.get(&(state.canvas, state.context)) {
+                Some(viewport) => {
+                    let space_transform = state.space.to_world(viewport);
+                    state.transform = space_transform * state.transform;
+                    state.clip = state.clip.map(|clip| clip.transformed(&space_transform));
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Whether bounds in world units overlap the view of the camera rendering the shape.
+    /// Shapes drawn for a target without a camera are kept.
+    pub(crate) fn is_in_view(&self, state: &ShapeState, bounds: Bounds) -> bool {
+        match self.viewports.get(&(state.canvas, state.context)) {
+            Some(viewport) => {
+                let view = viewport.bounds.transformed(&viewport.transform);
+                !view.intersection(&bounds).is_empty()
+            }
+            None => true,
+        }
+    }
+
+    /// Viewports of the cameras rendering into the window, by the context they render.
+    pub(crate) fn window_viewports(&self) -> impl Iterator<Item = (Option<&'static str>, &Viewport)> {
+        self.viewports.iter()
+            .filter(|((canvas, _), _)| canvas.is_none())
+            .map(|((_, context), viewport)| (*context, viewport))
+    }
+
+    /// Resolves the state, then detaches it from entities and cameras,
+    /// so the shape is drawn the same in an app where they do not exist.
+    #[cfg(feature = "serde")]
+    pub(crate) fn detach(&self, state: &mut ShapeState) -> bool {
+        if !self.resolve(state) {
+            return false;
+        }
+        state.attached_to = None;
+        state.space = CoordinateSpace::World;
+        true
+    }
+}
+
+/// Graphics whose shapes are drawn in the local space of an entity.
+/// Restores the previous attachment when dropped.
+pub type AttachedGraphics<'a> = ScopedGraphics<'a, Option<Entity>>;
+
+/// Projects the 3d transformation of an entity onto the xy plane.
+fn entity_transform(translation: Vec3, rotation: Quat, scale: Vec3) -> Mat3 {
+    let x_axis = rotation * Vec3::new(scale.x(), 0., 0.);
+    let y_axis = rotation * Vec3::new(0., scale.y(), 0.);
+
+    Mat3::from_cols(
+        Vec3::new(x_axis.x(), x_axis.y(), 0.),
+        Vec3::new(y_axis.x(), y_axis.y(), 0.),
+        Vec3::new(translation.x(), translation.y(), 1.),
+    )
+}
+
+/// Looks up the current transformation of every entity shapes are attached to
+/// and the viewport of every camera shapes can be drawn by.
+/// `Translation`, `Rotation` and `Scale` are read directly, so shapes do not lag
+/// behind until the transform systems have run. Those are relative to the parent of
+/// child entities, so children and entities with only a `Transform` use their world
+/// `Transform`, which the transform systems updated in `stage::POST_UPDATE`.
+pub fn resolve_system(
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+    parents: Query<&Parent>,
+    translations: Query<&Translation>,
+    rotations: Query<&Rotation>,
+    scales: Query<&Scale>,
+    non_uniform_scales: Query<&NonUniformScale>,
+    transforms: Query<&Transform>,
+    mut cameras: Query<(
+        Entity,
+        &Camera,
+        &OrthographicProjection,
+        Option<&GraphicsContext>,
+        Option<&CanvasCamera>,
+    )>,
+) {
+    let lookup = |entity: Entity| -> Option<Mat3> {
+        let is_child = parents.get::<Parent>(entity).is_ok();
+        let world_transform = || transforms.get::<Transform>(entity).ok().map(|transform| {
+            let value = transform.value;
+            Mat3::from_cols(
+                Vec3::new(value.x_axis().x(), value.x_axis().y(), 0.),
+                Vec3::new(value.y_axis().x(), value.y_axis().y(), 0.),
+                Vec3::new(value.w_axis().x(), value.w_axis().y(), 1.),
+            )
+        });
+
+        if is_child {
+            world_transform()
+        } else if let Ok(translation) = translations.get::<Translation>(entity) {
+            let rotation = rotations.get::<Rotation>(entity)
+                .map(|rotation| rotation.0)
+                .unwrap_or_else(|_| Quat::identity());
+            let scale = if let Ok(scale) = non_uniform_scales.get::<NonUniformScale>(entity) {
+                scale.0
+            } else {
+                let scale = scales.get::<Scale>(entity)
+                    .map(|scale| scale.0)
+                    .unwrap_or(1.);
+                Vec3::splat(scale)
+            };
+            Some(entity_transform(translation.0, rotation, scale))
+        } else {
+            world_transform()
+        }
+    };
+
+    let graphics = &mut *immediate_graphics;
+
+    let entities: Vec<Entity> = graphics.states()
+        .chain(graphics.groups.pending().flat_map(|group| group.states()))
+        .filter_map(|state| state.attached_to)
+        .collect();
+
+    graphics.resolver.entities.clear();
+
+    for entity in entities {
+        graphics.resolver.entities.insert(entity, lookup(entity));
+    }
+
+    graphics.resolver.viewports.clear();
+
+    for (entity, camera, projection, context, canvas_camera) in &mut cameras.iter() {
+        let canvas = canvas_camera.map(|camera| camera.texture);
+        let context = context.map(|context| context.name);
+
+        // the default context is rendered by the default 2d camera.
+        let is_default = canvas.is_none() && context.is_none();
+        if is_default && camera.name.as_deref() != Some(base::camera::CAMERA2D) {
+            continue;
+        }
+
+        let viewport = Viewport {
+            transform: lookup(entity).unwrap_or_else(Mat3::identity),
+            bounds: Bounds::new(
+                Vec2::new(projection.left, projection.bottom),
+                Vec2::new(projection.right, projection.top),
+            ),
+        };
+        graphics.resolver.viewports.insert((canvas, context), viewport);
+    }
+}
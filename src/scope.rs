@@ -0,0 +1,45 @@
+use std::ops::{DerefMut, Deref};
+use super::Immediate2DGraphics;
+
+/// Graphics drawing with one piece of state replaced, like the entity shapes are attached to
+/// or the coordinate space. Restores the previous state when dropped.
+pub struct ScopedGraphics<'a, T: Copy> {
+    graphics: &'a mut Immediate2DGraphics,
+    field: fn(&mut Immediate2DGraphics) -> &mut T,
+    previous: T,
+}
+
+impl<'a, T: Copy> ScopedGraphics<'a, T> {
+    /// Replaces the state in `field` with `value` until the returned graphics are dropped.
+    pub(crate) fn replace(
+        graphics: &'a mut Immediate2DGraphics,
+        field: fn(&mut Immediate2DGraphics) -> &mut T,
+        value: T,
+    ) -> Self {
+        let previous = std::mem::replace(field(graphics), value);
+        Self {
+            graphics,
+            field,
+            previous,
+        }
+    }
+}
+
+impl<'a, T: Copy> Deref for ScopedGraphics<'a, T> {
+    type Target = Immediate2DGraphics;
+    fn deref(&self) -> &Self::Target {
+        &*self.graphics
+    }
+}
+
+impl<'a, T: Copy> DerefMut for ScopedGraphics<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.graphics
+    }
+}
+
+impl<'a, T: Copy> Drop for ScopedGraphics<'a, T> {
+    fn drop(&mut self) {
+        *(self.field)(self.graphics) = self.previous;
+    }
+}
@@ -1,22 +1,84 @@
 #version 450
 layout(location = 0) out vec4 o_Target;
 layout(location = 0) in vec2 f_Uv;
+layout(location = 1) in vec2 f_Position;
 
 layout(set = 1, binding = 1) uniform CircleStyle {
     vec4 fill_color;
 	vec4 border_color;
+	vec4 uv_transform;
+	vec4 uv_offset;
+	vec4 shadow_color;
+	vec4 glow_color;
+	vec4 shadow;
+	vec4 clip;
 	float border_width;
+	float radius;
+	float extent;
+	float glow_radius;
 };
 
+# ifdef SHAPETEXTURE_TEXTURE
+layout(set = 1, binding = 2) uniform texture2D ShapeTexture_texture;
+layout(set = 1, binding = 3) uniform sampler ShapeTexture_texture_sampler;
+# endif
+
+vec4 fill(vec2 shape_uv) {
+	vec4 color = fill_color;
+# ifdef SHAPETEXTURE_TEXTURE
+	vec2 tex_uv = mat2(uv_transform.xy, uv_transform.zw) * (shape_uv - 0.5) + 0.5 + uv_offset.xy;
+	if (uv_offset.z > 0.5) {
+		tex_uv = fract(tex_uv);
+	}
+	if (tex_uv.x < 0. || tex_uv.y < 0. || tex_uv.x > 1. || tex_uv.y > 1.) {
+		color = vec4(0.);
+	} else {
+		color *= texture(sampler2D(ShapeTexture_texture, ShapeTexture_texture_sampler), tex_uv);
+	}
+# endif
+	return color;
+}
+
+// Shadow and glow outside of the shape, from the distances to its edge.
+vec4 effects(float shadow_dist, float dist) {
+	float blur = max(shadow.z, 0.0001);
+	float shadow_alpha = shadow_color.a * (1. - smoothstep(-blur, blur, shadow_dist));
+	float glow_alpha = 0.;
+	if (glow_radius > 0.) {
+		glow_alpha = glow_color.a * (1. - smoothstep(0., glow_radius, dist));
+	}
+
+	float alpha = glow_alpha + shadow_alpha * (1. - glow_alpha);
+	if (alpha <= 0.) {
+		return vec4(0.);
+	}
+
+	vec3 color = glow_color.rgb * glow_alpha + shadow_color.rgb * shadow_alpha * (1. - glow_alpha);
+	return vec4(color / alpha, alpha);
+}
+
 void main() {
-    vec2 uv = (f_Uv - 0.5) * 2;
+	if (f_Position.x < clip.x || f_Position.y < clip.y
+		|| f_Position.x > clip.z || f_Position.y > clip.w) {
+		discard;
+	}
+
+	// position relative to the center in world units, y pointing down like the uvs.
+	vec2 pos = (f_Uv - 0.5) * 2. * extent;
+	vec2 uv = pos / radius;
 	float square_len = uv.x * uv.x + uv.y * uv.y;
 	if (square_len < 1.) {
-		o_Target = fill_color;
+		o_Target = fill(uv / 2. + 0.5);
 		if (square_len > 1 - border_width) {
 			o_Target = border_color;
 		}
+		if (o_Target.a <= 0.) {
+			discard;
+		}
 	} else {
-		discard;
+		o_Target = effects(length(pos - shadow.xy) - radius, length(pos) - radius);
+		if (o_Target.a <= 0.) {
+			discard;
+		}
 	}
 }
@@ -0,0 +1,28 @@
+#version 450
+layout(location = 0) out vec4 o_Target;
+layout(location = 0) in vec2 f_Uv;
+layout(location = 1) in vec2 f_Position;
+
+layout(set = 1, binding = 1) uniform ImageStyle {
+	vec4 tint;
+	vec4 uv_rect;
+	vec4 clip;
+};
+
+layout(set = 1, binding = 2) uniform texture2D ShapeTexture_texture;
+layout(set = 1, binding = 3) uniform sampler ShapeTexture_texture_sampler;
+
+void main() {
+	if (f_Position.x < clip.x || f_Position.y < clip.y
+		|| f_Position.x > clip.z || f_Position.y > clip.w) {
+		discard;
+	}
+
+	vec2 uv = mix(uv_rect.xy, uv_rect.zw, f_Uv);
+
+	o_Target = tint * texture(sampler2D(ShapeTexture_texture, ShapeTexture_texture_sampler), uv);
+
+	if (o_Target.a <= 0.) {
+		discard;
+	}
+}
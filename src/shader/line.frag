@@ -1,15 +1,22 @@
 #version 450
 layout(location = 0) out vec4 o_Target;
 layout(location = 0) in vec2 f_Uv;
+layout(location = 1) in vec2 f_Position;
 
 layout(set = 1, binding = 1) uniform LineStyle {
 	vec4 color;
+	vec4 clip;
 	float width;
 	float height;
 	float stroke;
 };
 
 void main() {
+	if (f_Position.x < clip.x || f_Position.y < clip.y
+		|| f_Position.x > clip.z || f_Position.y > clip.w) {
+		discard;
+	}
+
 	vec2 pos = vec2(f_Uv.x * width, f_Uv.y * height);
 
 	vec2 start = vec2(stroke, height / 2.);
@@ -3,6 +3,7 @@ layout(location = 0) in vec3 Vertex_Position;
 layout(location = 1) in vec2 Vertex_Uv;
 
 layout(location = 0) out vec2 f_Uv;
+layout(location = 1) out vec2 f_Position;
 
 layout(set = 0, binding = 0) uniform Camera {
     mat4 ViewProj;
@@ -14,5 +15,7 @@ layout(set = 1, binding = 0) uniform Transform {
 
 void main() {
     f_Uv = Vertex_Uv;
-    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+    vec4 position = Model * vec4(Vertex_Position, 1.0);
+    f_Position = position.xy;
+    gl_Position = ViewProj * position;
 }
@@ -0,0 +1,164 @@
+//! Golden image tests for shapes, rendered with the cpu rasterizer.
+//!
+//! Goldens are written with `UPDATE_SNAPSHOTS=1 cargo test` and committed with the tests
+//! that use them. A missing golden fails the test instead of being written.
+
+use bevy::prelude::*;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use super::Immediate2DGraphics;
+use super::bounds::Bounds;
+use super::raster::Raster;
+
+/// How snapshots are rendered and compared.
+#[derive(Debug, Clone)]
+pub struct SnapshotOptions {
+    /// World space area that is rendered.
+    pub viewport: Bounds,
+    pub width: u32,
+    pub height: u32,
+    /// Largest difference of a channel for two pixels to be considered equal.
+    pub tolerance: u8,
+    /// Number of pixels that may differ before the comparison fails.
+    pub max_different_pixels: usize,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self {
+            viewport: Bounds::from_center_size(Vec2::zero(), Vec2::new(512., 512.)),
+            width: 256,
+            height: 256,
+            tolerance: 2,
+            max_different_pixels: 0,
+        }
+    }
+}
+
+fn read_png(path: &Path) -> io::Result<Raster> {
+    let decoder = png::Decoder::new(File::open(path)?);
+    let (info, mut reader) = decoder.read_info()
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "snapshots have to be 8 bit rgba"));
+    }
+
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    Ok(Raster {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+/// Marks differing pixels red on a faded copy of the expected image.
+fn diff_image(expected: &Raster, actual: &Raster, tolerance: u8) -> (Raster, usize) {
+    let mut different = 0;
+    let pixels = expected.pixels.chunks(4)
+        .zip(actual.pixels.chunks(4))
+        .flat_map(|(expected, actual)| {
+            let differs = expected.iter()
+                .zip(actual.iter())
+                .any(|(a, b)| (*a as i16 - *b as i16).abs() > tolerance as i16);
+            if differs {
+                different += 1;
+                vec![255, 0, 0, 255]
+            } else {
+                let gray = ((expected[0] as u16 + expected[1] as u16 + expected[2] as u16) / 3) as u8;
+                let faded = 128 + gray / 2;
+                vec![faded, faded, faded, 255]
+            }
+        })
+        .collect();
+
+    let diff = Raster {
+        width: expected.width,
+        height: expected.height,
+        pixels,
+    };
+    (diff, different)
+}
+
+/// Renders the shapes and compares them with `<directory>/<name>.png`.
+/// The snapshot is only written when `UPDATE_SNAPSHOTS=1` is set, a missing snapshot fails.
+/// On mismatch, the rendered image and a diff are written next to it
+/// as `<name>.actual.png` and `<name>.diff.png`.
+pub fn check_snapshot(
+    graphics: &Immediate2DGraphics,
+    directory: impl AsRef<Path>,
+    name: &str,
+    options: &SnapshotOptions,
+) -> Result<(), String> {
+    let directory = directory.as_ref();
+    let path = |suffix: &str| -> PathBuf { directory.join(format!("{}{}.png", name, suffix)) };
+
+    let textures = Assets::<Texture>::default();
+    let actual = graphics.rasterize(options.viewport, options.width, options.height, &textures);
+
+    let expected_path = path("");
+    if std::env::var("UPDATE_SNAPSHOTS").map_or(false, |update| update == "1") {
+        std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        return actual.save_png(&expected_path).map_err(|error| error.to_string());
+    }
+
+    if !expected_path.exists() {
+        std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        actual.save_png(path(".actual")).map_err(|error| error.to_string())?;
+        return Err(format!(
+            "snapshot {} does not exist, run with UPDATE_SNAPSHOTS=1 to create it from {}",
+            expected_path.display(), path(".actual").display(),
+        ));
+    }
+
+    let expected = read_png(&expected_path)
+        .map_err(|error| format!("could not read snapshot {}: {}", expected_path.display(), error))?;
+
+    if expected.width != actual.width || expected.height != actual.height {
+        actual.save_png(path(".actual")).map_err(|error| error.to_string())?;
+        return Err(format!(
+            "snapshot {} is {}x{}, but {}x{} was rendered",
+            name, expected.width, expected.height, actual.width, actual.height,
+        ));
+    }
+
+    let (diff, different) = diff_image(&expected, &actual, options.tolerance);
+    if different > options.max_different_pixels {
+        actual.save_png(path(".actual")).map_err(|error| error.to_string())?;
+        diff.save_png(path(".diff")).map_err(|error| error.to_string())?;
+        return Err(format!(
+            "snapshot {} differs in {} pixels, see {}",
+            name, different, path(".diff").display(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Asserts that shapes render like the png stored in `snapshots/` of the calling crate.
+///
+/// ```ignore
+/// let mut graphics = Immediate2DGraphics::default();
+/// graphics.fill_rectangle(0., 0.).with_border(Color::RED, 0.2);
+/// assert_snapshot!(graphics, "rectangle_border");
+/// ```
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($graphics:expr, $name:expr) => {
+        $crate::assert_snapshot!($graphics, $name, &$crate::snapshot::SnapshotOptions::default())
+    };
+    ($graphics:expr, $name:expr, $options:expr) => {
+        if let Err(error) = $crate::snapshot::check_snapshot(
+            &$graphics,
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots"),
+            $name,
+            $options,
+        ) {
+            panic!("{}", error);
+        }
+    };
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use super::scope::ScopedGraphics;
+use super::bounds::Bounds;
+use super::transform;
+
+/// Point of the viewport anchored coordinates are relative to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position of the anchor in normalized viewport coordinates, y pointing down.
+    pub fn position(&self) -> Vec2 {
+        match self {
+            Anchor::TopLeft => Vec2::new(0., 0.),
+            Anchor::TopCenter => Vec2::new(0.5, 0.),
+            Anchor::TopRight => Vec2::new(1., 0.),
+            Anchor::CenterLeft => Vec2::new(0., 0.5),
+            Anchor::Center => Vec2::new(0.5, 0.5),
+            Anchor::CenterRight => Vec2::new(1., 0.5),
+            Anchor::BottomLeft => Vec2::new(0., 1.),
+            Anchor::BottomCenter => Vec2::new(0.5, 1.),
+            Anchor::BottomRight => Vec2::new(1., 1.),
+        }
+    }
+}
+
+/// Space the coordinates and sizes of shapes are given in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordinateSpace {
+    /// World units, as seen by the camera.
+    World,
+    /// Logical pixels from the top left corner of the viewport, y pointing down.
+    Pixels,
+    /// `0..1` from the top left to the bottom right corner of the viewport.
+    /// Circles become ellipses unless the viewport is square.
+    Normalized,
+    /// Logical pixels from an anchor of the viewport, y pointing down.
+    Anchored(Anchor),
+}
+
+impl Default for CoordinateSpace {
+    fn default() -> Self {
+        CoordinateSpace::World
+    }
+}
+
+/// Area seen by a camera, used to resolve screen space coordinates.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Viewport {
+    /// Transformation of the camera.
+    pub transform: Mat3,
+    /// Bounds of the projection in the local space of the camera.
+    pub bounds: Bounds,
+}
+
+impl CoordinateSpace {
+    /// Transformation from this space into world space.
+    pub(crate) fn to_world(&self, viewport: &Viewport) -> Mat3 {
+        let size = viewport.bounds.size();
+        let top_left = Vec2::new(viewport.bounds.min.x(), viewport.bounds.max.y());
+
+        let local = match self {
+            CoordinateSpace::World => return Mat3::identity(),
+            CoordinateSpace::Pixels => transform::affine(Vec2::new(1., -1.), 0., top_left),
+            CoordinateSpace::Normalized => transform::affine(Vec2::new(size.x(), -size.y()), 0., top_left),
+            CoordinateSpace::Anchored(anchor) => {
+                let anchor = anchor.position() * size;
+                let origin = Vec2::new(top_left.x() + anchor.x(), top_left.y() - anchor.y());
+                transform::affine(Vec2::new(1., -1.), 0., origin)
+            }
+        };
+
+        viewport.transform * local
+    }
+}
+
+/// Graphics whose shapes are drawn in another coordinate space.
+/// Restores the previous space when dropped.
+pub type SpaceGraphics<'a> = ScopedGraphics<'a, CoordinateSpace>;
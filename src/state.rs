@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use super::bounds::Bounds;
+use super::space::CoordinateSpace;
+use super::lifetime::Lifetime;
+use super::picking::ShapeId;
+
+/// Distance on the z axis between two consecutively submitted shapes.
+/// Later shapes are drawn on top of earlier ones.
+pub(crate) const DEPTH_STEP: f32 = 0.001;
+
+/// Distance on the z axis between two layers.
+/// Shapes of a higher layer are drawn on top of all shapes of lower layers.
+pub(crate) const LAYER_DEPTH: f32 = 10.;
+
+/// Highest layer in front of the far plane of the default 2d camera, which sees up to z 1000.
+pub(crate) const MAX_LAYER: u32 = 99;
+
+/// State captured when a shape is submitted, shared by all kinds of shapes.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShapeState {
+    /// Position on the z axis, derived from the submission order.
+    pub z: f32,
+    /// Transformation from the transform stack, applied on top of the shape's own.
+    pub transform: Mat3,
+    /// Entity in whose local space the shape is drawn.
+    /// Entities are only valid in the running app, so recordings drop them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub attached_to: Option<Entity>,
+    /// Rectangle the shape is clipped to, in the same space as `transform`.
+    pub clip: Option<Bounds>,
+    /// Canvas the shape is drawn into, `None` for the screen.
+    pub canvas: Option<Handle<Texture>>,
+    /// Named context the shape belongs to, `None` for the default context.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub context: Option<&'static str>,
+    /// Space the coordinates of the shape are given in.
+    pub space: CoordinateSpace,
+    /// How long the shape is drawn, `None` for only the frame it was submitted in.
+    pub lifetime: Option<Lifetime>,
+    /// Id the shape is hit tested with, `None` if it can not be hit.
+    pub id: Option<ShapeId>,
+    /// Whether interaction events are sent for the shape.
+    pub interactive: bool,
+}
+
+impl ShapeState {
+    /// Counts down the lifetime at the end of a frame that took `delta` seconds.
+    /// Returns whether the shape is drawn in the next frame.
+    pub(crate) fn tick(&mut self, delta: f32) -> bool {
+        match &mut self.lifetime {
+            Some(lifetime) => lifetime.tick(delta),
+            None => false,
+        }
+    }
+
+    /// Limits bounds in the space of `transform` to the clip rectangle.
+    pub(crate) fn clipped(&self, bounds: Bounds) -> Bounds {
+        match self.clip {
+            Some(clip) => bounds.intersection(&clip),
+            None => bounds,
+        }
+    }
+
+    /// Factor the alpha of the shape is multiplied with.
+    pub(crate) fn opacity(&self) -> f32 {
+        self.lifetime.map(|lifetime| lifetime.opacity()).unwrap_or(1.)
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+use std::time::Duration;
+use super::Immediate2DGraphics;
+use super::state::MAX_LAYER;
+use super::space::CoordinateSpace;
+use super::labels::{HorizontalAlign, VerticalAlign};
+
+/// Number of shapes of every kind.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShapeCounts {
+    pub circles: usize,
+    pub rectangles: usize,
+    pub lines: usize,
+    pub images: usize,
+    pub labels: usize,
+}
+
+impl ShapeCounts {
+    pub fn total(&self) -> usize {
+        self.circles + self.rectangles + self.lines + self.images + self.labels
+    }
+}
+
+/// Statistics of the shapes processed in the last frame.
+#[derive(Debug, Clone, Default)]
+pub struct Immediate2DGraphicsStats {
+    /// Shapes drawn this frame, including shapes kept by their lifetime and replayed ones.
+    pub submitted: ShapeCounts,
+    /// Existing entities reused to draw a shape.
+    pub pooled: usize,
+    /// Entities spawned because there were more shapes than existing entities,
+    /// including the entities of new retained groups.
+    pub spawned: usize,
+    /// Existing entities hidden because there were fewer shapes than entities.
+    pub hidden: usize,
+    /// Shapes that were not drawn because they are outside the view of their camera.
+    pub culled: usize,
+    /// Draw calls issued for shapes, one for every shape and one for every glyph of text.
+    pub draw_calls: usize,
+    /// Vertices drawn for shapes. Every shape and glyph is drawn as a quad,
+    /// so no shape is tessellated.
+    pub vertices: usize,
+    /// CPU time spent in the systems turning shapes into entities.
+    pub update_time: Duration,
+}
+
+impl Immediate2DGraphicsStats {
+    /// Counts quads drawn with one draw call each.
+    pub(crate) fn add_quads(&mut self, quads: usize) {
+        self.draw_calls += quads;
+        self.vertices += quads * 4;
+    }
+}
+
+/// On-screen overlay showing the statistics of the last frame in a corner of the default camera.
+/// Text is only drawn once a font is set here or with `set_default_font`.
+#[derive(Debug, Clone, Default)]
+pub struct StatsOverlay {
+    pub enabled: bool,
+    /// Key toggling the overlay, none by default.
+    pub toggle_key: Option<KeyCode>,
+    pub font: Option<Handle<Font>>,
+}
+
+/// Layer the overlay is drawn on. It is the highest layer the 2d camera sees,
+/// which `set_layer` reserves for the overlay.
+const OVERLAY_LAYER: u32 = MAX_LAYER;
+const OVERLAY_MARGIN: f32 = 8.;
+const OVERLAY_PADDING: f32 = 8.;
+const OVERLAY_WIDTH: f32 = 260.;
+const OVERLAY_FONT_SIZE: f32 = 14.;
+const OVERLAY_LINE_HEIGHT: f32 = 18.;
+
+fn overlay_lines(stats: &Immediate2DGraphicsStats) -> Vec<String> {
+    let submitted = &stats.submitted;
+    vec![
+        format!("shapes {}", submitted.total()),
+        format!(
+            "  {} circles {} rects {} lines",
+            submitted.circles, submitted.rectangles, submitted.lines,
+        ),
+        format!("  {} images {} labels", submitted.images, submitted.labels),
+        format!("culled {}", stats.culled),
+        format!("entities {} pooled {} spawned {} hidden", stats.pooled, stats.spawned, stats.hidden),
+        format!("draw calls {}", stats.draw_calls),
+        format!("vertices {}", stats.vertices),
+        format!("update {:.2} ms", stats.update_time.as_secs_f64() * 1000.),
+    ]
+}
+
+/// Toggles the overlay and draws the statistics of the last frame, before they are restarted.
+pub fn stats_overlay_system(
+    mut overlay: ResMut<StatsOverlay>,
+    keys: Res<Input<KeyCode>>,
+    stats: Res<Immediate2DGraphicsStats>,
+    mut immediate_graphics: ResMut<Immediate2DGraphics>,
+) {
+    if let Some(key) = overlay.toggle_key {
+        if keys.just_pressed(key) {
+            overlay.enabled = !overlay.enabled;
+        }
+    }
+    if !overlay.enabled {
+        return;
+    }
+
+    let lines = overlay_lines(&stats);
+    let height = lines.len() as f32 * OVERLAY_LINE_HEIGHT + 2. * OVERLAY_PADDING;
+
+    let previous_layer = immediate_graphics.layer;
+    immediate_graphics.set_layer(OVERLAY_LAYER);
+    {
+        let mut graphics = immediate_graphics.in_space(CoordinateSpace::Pixels);
+
+        graphics
+            .fill_rectangle(OVERLAY_MARGIN + OVERLAY_WIDTH / 2., OVERLAY_MARGIN + height / 2.)
+            .with_width(OVERLAY_WIDTH)
+            .with_height(height)
+            .with_color(Color::rgba(0., 0., 0., 0.7));
+
+        for (index, line) in lines.iter().enumerate() {
+            let label = graphics
+                .draw_text(
+                    line,
+                    OVERLAY_MARGIN + OVERLAY_PADDING,
+                    OVERLAY_MARGIN + OVERLAY_PADDING + index as f32 * OVERLAY_LINE_HEIGHT,
+                )
+                .with_size(OVERLAY_FONT_SIZE)
+                .with_color(Color::WHITE)
+                .with_align(HorizontalAlign::Left, VerticalAlign::Top);
+
+            if let Some(font) = overlay.font {
+                label.with_font(font);
+            }
+        }
+    }
+    immediate_graphics.set_layer(previous_layer);
+}
+
+/// Restarts the statistics before the shapes of a frame are processed.
+pub fn stats_begin_system(mut stats: ResMut<Immediate2DGraphicsStats>) {
+    *stats = Immediate2DGraphicsStats::default();
+}
@@ -0,0 +1,340 @@
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::io;
+use std::path::PathBuf;
+use super::Immediate2DGraphics;
+use super::bounds::Bounds;
+use super::state::ShapeState;
+use super::effects::Effects;
+use super::labels::{HorizontalAlign, VerticalAlign};
+use super::transform;
+
+/// Frames to be written to SVG files by `svg_export_system`.
+#[derive(Debug, Default)]
+pub struct SvgExport {
+    requests: Vec<(PathBuf, Bounds)>,
+    errors: Vec<(PathBuf, io::Error)>,
+}
+
+impl SvgExport {
+    /// Write the shapes of the current frame inside `viewport` to a file.
+    pub fn capture(&mut self, path: impl Into<PathBuf>, viewport: Bounds) {
+        self.requests.push((path.into(), viewport));
+    }
+
+    /// Files that could not be written since the last call, with their errors.
+    pub fn take_errors(&mut self) -> Vec<(PathBuf, io::Error)> {
+        std::mem::take(&mut self.errors)
+    }
+}
+
+fn rgb(color: Color) -> String {
+    format!(
+        "rgb({},{},{})",
+        (color.r.max(0.).min(1.) * 255.).round(),
+        (color.g.max(0.).min(1.) * 255.).round(),
+        (color.b.max(0.).min(1.) * 255.).round(),
+    )
+}
+
+fn paint(attribute: &str, color: Color, opacity: f32) -> String {
+    format!(r#"{0}="{1}" {0}-opacity="{2}""#, attribute, rgb(color), color.a * opacity)
+}
+
+fn flood(color: Color, opacity: f32) -> String {
+    format!(r#"flood-color="{}" flood-opacity="{}""#, rgb(color), color.a * opacity)
+}
+
+fn matrix(affine: &Mat3) -> String {
+    let x_axis = affine.x_axis();
+    let y_axis = affine.y_axis();
+    let translation = affine.z_axis();
+    format!(
+        r#"transform="matrix({} {} {} {} {} {})""#,
+        x_axis.x(), x_axis.y(), y_axis.x(), y_axis.y(), translation.x(), translation.y(),
+    )
+}
+
+/// Collects the elements of all shapes, to be sorted by their depth.
+struct SvgWriter<'a> {
+    graphics: &'a Immediate2DGraphics,
+    /// Transformation from world space into the space of the svg, with y pointing down.
+    to_svg: Mat3,
+    viewport: Bounds,
+    clip_paths: Vec<Bounds>,
+    /// Filters drawing shadows and glows, referenced as `effects<index>`.
+    filters: Vec<String>,
+    elements: Vec<(f32, String)>,
+}
+
+impl<'a> SvgWriter<'a> {
+    fn resolve(&self, state: &ShapeState) -> Option<ShapeState> {
+        let mut state = state.clone();
+        if self.graphics.resolver.resolve(&mut state) {
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    /// Adds an element, wrapped in a group clipping it if needed.
+    fn push(&mut self, state: &ShapeState, element: String) {
+        let element = if let Some(clip) = state.clip {
+            let id = self.clip_paths.len();
+            self.clip_paths.push(clip);
+            format!(r#"<g clip-path="url(#clip{})">{}</g>"#, id, element)
+        } else {
+            element
+        };
+        self.elements.push((state.z, element));
+    }
+
+    /// Adds a filter drawing the shadow and glow of a shape below it and returns the
+    /// attribute applying it, empty without effects. `bounds` are in the space of the element
+    /// the filter is applied to, which `affine` maps to world units. The soft edges are
+    /// gaussian blurs, so they only approximate the shaders.
+    fn effects_filter(&mut self, effects: &Effects, affine: &Mat3, bounds: Bounds, opacity: f32) -> String {
+        if effects.shadow.is_none() && effects.glow.is_none() {
+            return String::new();
+        }
+
+        let id = self.filters.len();
+        let region = bounds.expanded(effects.margin(affine));
+        let shadow_offset = effects.shadow_offset(affine);
+        let mut filter = format!(
+            r#"<filter id="effects{}" filterUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}">"#,
+            id, region.min.x(), region.min.y(), region.size().x(), region.size().y(),
+        );
+        let mut merge = String::new();
+
+        if let Some(shadow) = effects.shadow {
+            write!(
+                filter,
+                r#"<feGaussianBlur in="SourceAlpha" stdDeviation="{}"/><feOffset dx="{}" dy="{}" result="shadowAlpha"/><feFlood {}/><feComposite in2="shadowAlpha" operator="in" result="shadow"/>"#,
+                shadow.blur / 2., shadow_offset.x(), shadow_offset.y(), flood(shadow.color, opacity),
+            ).unwrap();
+            merge.push_str(r#"<feMergeNode in="shadow"/>"#);
+        }
+        if let Some(glow) = effects.glow {
+            write!(
+                filter,
+                r#"<feGaussianBlur in="SourceAlpha" stdDeviation="{}" result="glowAlpha"/><feFlood {}/><feComposite in2="glowAlpha" operator="in" result="glow"/>"#,
+                glow.radius / 2., flood(glow.color, opacity),
+            ).unwrap();
+            merge.push_str(r#"<feMergeNode in="glow"/>"#);
+        }
+        write!(filter, r#"<feMerge>{}<feMergeNode in="SourceGraphic"/></feMerge></filter>"#, merge).unwrap();
+
+        self.filters.push(filter);
+        format!(r#"filter="url(#effects{})""#, id)
+    }
+
+    fn circles(&mut self) {
+        for circle in self.graphics.circles.iter() {
+            let state = match self.resolve(&circle.state) {
+                Some(state) => state,
+                None => continue,
+            };
+            let opacity = state.opacity();
+            let affine = matrix(&(self.to_svg * state.transform));
+            let (x, y, radius) = (circle.pos.x(), circle.pos.y(), circle.radius);
+            let filter = self.effects_filter(
+                &circle.effects,
+                &state.transform,
+                Bounds::from_center_size(circle.pos, Vec2::splat(radius * 2.)),
+                opacity,
+            );
+
+            let element = if let Some(stroke) = circle.outline {
+                let stroke = stroke.min(radius);
+                format!(
+                    r#"<circle cx="{}" cy="{}" r="{}" fill="none" {} stroke-width="{}" {} {}/>"#,
+                    x, y, radius - stroke / 2., paint("stroke", circle.style.border_color, opacity), stroke, filter, affine,
+                )
+            } else {
+                // the border width is the part of the squared radius covered by the border.
+                let border = radius * (1. - (1. - circle.style.border_width.max(0.).min(1.)).sqrt());
+                let mut element = format!(
+                    r#"<circle cx="{}" cy="{}" r="{}" {} {} {}/>"#,
+                    x, y, radius, paint("fill", circle.style.fill_color, opacity), filter, affine,
+                );
+                if border > 0. {
+                    write!(
+                        element,
+                        r#"<circle cx="{}" cy="{}" r="{}" fill="none" {} stroke-width="{}" {}/>"#,
+                        x, y, radius - border / 2., paint("stroke", circle.style.border_color, opacity), border, affine,
+                    ).unwrap();
+                }
+                element
+            };
+            self.push(&state, element);
+        }
+    }
+
+    fn rectangles(&mut self) {
+        for rectangle in self.graphics.rectangles.iter() {
+            let state = match self.resolve(&rectangle.state) {
+                Some(state) => state,
+                None => continue,
+            };
+            let opacity = state.opacity();
+            let local = state.transform * transform::affine(Vec2::one(), rectangle.rotation, rectangle.pos);
+            let affine = matrix(&(self.to_svg * local));
+            let size = rectangle.dimensions;
+            let filter = self.effects_filter(
+                &rectangle.effects,
+                &local,
+                Bounds::from_center_size(Vec2::zero(), size),
+                opacity,
+            );
+
+            let rect = |inset: f32, attributes: String| format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" {} {}/>"#,
+                -size.x() / 2. + inset, -size.y() / 2. + inset,
+                (size.x() - 2. * inset).max(0.), (size.y() - 2. * inset).max(0.),
+                attributes, affine,
+            );
+
+            let element = if let Some(stroke) = rectangle.outline {
+                rect(stroke / 2., format!(
+                    r#"fill="none" {} stroke-width="{}" {}"#,
+                    paint("stroke", rectangle.style.border_color, opacity), stroke, filter,
+                ))
+            } else {
+                // the border width is relative to half the size of the rectangle.
+                let border = rectangle.style.border_width.x() * size.x() / 2.;
+                let mut element = rect(0., format!("{} {}", paint("fill", rectangle.style.fill_color, opacity), filter));
+                if border > 0. {
+                    element.push_str(&rect(border / 2., format!(
+                        r#"fill="none" {} stroke-width="{}""#,
+                        paint("stroke", rectangle.style.border_color, opacity), border,
+                    )));
+                }
+                element
+            };
+            self.push(&state, element);
+        }
+    }
+
+    fn lines(&mut self) {
+        for line in self.graphics.lines.iter() {
+            let state = match self.resolve(&line.state) {
+                Some(state) => state,
+                None => continue,
+            };
+            let element = format!(
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {} stroke-width="{}" stroke-linecap="round" {}/>"#,
+                line.start.x(), line.start.y(), line.stop.x(), line.stop.y(),
+                paint("stroke", line.style.color, state.opacity()), line.style.stroke * 2.,
+                matrix(&(self.to_svg * state.transform)),
+            );
+            self.push(&state, element);
+        }
+    }
+
+    /// Text is written with the generic font of the svg viewer, so its metrics differ.
+    fn labels(&mut self) {
+        for label in self.graphics.labels.iter() {
+            let state = match self.resolve(&label.state) {
+                Some(state) => state,
+                None => continue,
+            };
+            let affine = self.to_svg * state.transform;
+            let pos = transform::transform_point(&affine, label.pos);
+            let size = label.size * transform::scale_factor(&affine);
+
+            let anchor = match label.horizontal_align {
+                HorizontalAlign::Left => "start",
+                HorizontalAlign::Center => "middle",
+                HorizontalAlign::Right => "end",
+            };
+            let baseline = match label.vertical_align {
+                VerticalAlign::Top => "hanging",
+                VerticalAlign::Baseline => "alphabetic",
+                VerticalAlign::Bottom => "text-after-edge",
+            };
+            let text = label.value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+
+            let element = format!(
+                r#"<text x="{}" y="{}" font-size="{}" text-anchor="{}" dominant-baseline="{}" {}>{}</text>"#,
+                pos.x(), pos.y(), size, anchor, baseline, paint("fill", label.color, state.opacity()), text,
+            );
+            self.push(&state, element);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.elements.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let size = self.viewport.size();
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            size.x(), size.y(),
+        );
+        svg.push('\n');
+
+        if !self.clip_paths.is_empty() || !self.filters.is_empty() {
+            svg.push_str("<defs>\n");
+            for (id, clip) in self.clip_paths.iter().enumerate() {
+                let min = transform::transform_point(&self.to_svg, Vec2::new(clip.min.x(), clip.max.y()));
+                let size = clip.size();
+                writeln!(
+                    svg,
+                    r#"<clipPath id="clip{}"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath>"#,
+                    id, min.x(), min.y(), size.x().max(0.), size.y().max(0.),
+                ).unwrap();
+            }
+            for filter in self.filters.iter() {
+                svg.push_str(filter);
+                svg.push('\n');
+            }
+            svg.push_str("</defs>\n");
+        }
+
+        for (_, element) in self.elements {
+            svg.push_str(&element);
+            svg.push('\n');
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Serializes the submitted circles, rectangles, lines and text inside `viewport`.
+pub(crate) fn to_svg(graphics: &Immediate2DGraphics, viewport: Bounds) -> String {
+    let to_svg = Mat3::from_cols(
+        Vec3::new(1., 0., 0.),
+        Vec3::new(0., -1., 0.),
+        Vec3::new(-viewport.min.x(), viewport.max.y(), 1.),
+    );
+
+    let mut writer = SvgWriter {
+        graphics,
+        to_svg,
+        viewport,
+        clip_paths: Vec::new(),
+        filters: Vec::new(),
+        elements: Vec::new(),
+    };
+    writer.circles();
+    writer.rectangles();
+    writer.lines();
+    writer.labels();
+    writer.finish()
+}
+
+/// Writes the frames requested with `SvgExport::capture`, once the shapes are resolved.
+pub fn svg_export_system(
+    mut export: ResMut<SvgExport>,
+    immediate_graphics: Res<Immediate2DGraphics>,
+) {
+    let export = &mut *export;
+    for (path, viewport) in export.requests.drain(..) {
+        if let Err(error) = std::fs::write(&path, immediate_graphics.to_svg(viewport)) {
+            export.errors.push((path, error));
+        }
+    }
+}
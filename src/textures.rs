@@ -0,0 +1,130 @@
+use bevy::{
+    render::{
+        pipeline::{CullMode, PipelineDescriptor},
+        shader::ShaderStages,
+        renderer::RenderResources,
+        render_graph::{RenderGraph, base, RenderResourcesNode}
+    },
+    prelude::*,
+};
+
+/// Shader def that enables the texture bindings in the shape shaders.
+pub(crate) const TEXTURE_SHADER_DEF: &str = "SHAPETEXTURE_TEXTURE";
+
+pub(crate) const SHAPE_TEXTURE_NODE: &str = "ShapeTexture";
+
+/// Texture bound next to the style of a shape.
+/// Binds as `ShapeTexture_texture` and `ShapeTexture_texture_sampler`.
+#[derive(Debug, Default, RenderResources)]
+pub struct ShapeTexture {
+    pub texture: Option<Handle<Texture>>,
+}
+
+/// How a texture is mapped onto the bounding box of a shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureFit {
+    /// Stretch the texture over the whole shape.
+    Stretch,
+    /// Scale the texture uniformly so it is entirely visible.
+    Fit,
+    /// Scale the texture uniformly so it covers the entire shape.
+    Fill,
+    /// Repeat the texture, each tile having the given size in world units.
+    Tile(Vec2),
+}
+
+impl Default for TextureFit {
+    fn default() -> Self {
+        TextureFit::Stretch
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UvTransform {
+    pub fit: TextureFit,
+    /// Offset in texture coordinates.
+    pub offset: Vec2,
+    /// Rotation of the texture in radians.
+    pub rotation: f32,
+}
+
+impl UvTransform {
+    /// Calculates the `uv_transform` and `uv_offset` uniforms of the shape shaders.
+    /// The shader computes `mat2(uv_transform) * (uv - 0.5) + 0.5 + uv_offset.xy`
+    /// and wraps the result if `uv_offset.z` is set.
+    pub(crate) fn uniforms(&self, shape_size: Vec2, texture_size: Option<Vec2>) -> (Vec4, Vec4) {
+        let texture_size = texture_size.unwrap_or(shape_size);
+
+        // size of the texture in world units
+        let (scaled_size, wrap) = match self.fit {
+            TextureFit::Stretch => (shape_size, false),
+            TextureFit::Fit => {
+                let scale = f32::min(
+                    shape_size.x() / texture_size.x(),
+                    shape_size.y() / texture_size.y(),
+                );
+                (texture_size * scale, false)
+            }
+            TextureFit::Fill => {
+                let scale = f32::max(
+                    shape_size.x() / texture_size.x(),
+                    shape_size.y() / texture_size.y(),
+                );
+                (texture_size * scale, false)
+            }
+            TextureFit::Tile(tile_size) => (tile_size, true),
+        };
+
+        // M = scale(1 / scaled_size) * rotation(-rotation) * scale(shape_size)
+        let (sin, cos) = self.rotation.sin_cos();
+        let uv_transform = Vec4::new(
+            cos * shape_size.x() / scaled_size.x(),
+            -sin * shape_size.x() / scaled_size.y(),
+            sin * shape_size.y() / scaled_size.x(),
+            cos * shape_size.y() / scaled_size.y(),
+        );
+
+        let uv_offset = Vec4::new(
+            self.offset.x(),
+            self.offset.y(),
+            if wrap { 1. } else { 0. },
+            0.,
+        );
+
+        (uv_transform, uv_offset)
+    }
+}
+
+/// Enables or disables the texture bindings of the given pipelines.
+/// Pooled entities switch between textured and plain shapes, so the
+/// shader def has to be removed again as well.
+pub(crate) fn set_texture_shader_def(render_pipelines: &mut RenderPipelines, enabled: bool) {
+    for pipeline in render_pipelines.pipelines.iter_mut() {
+        let shader_defs = &mut pipeline.specialization.shader_specialization.shader_defs;
+        if enabled {
+            shader_defs.insert(TEXTURE_SHADER_DEF.to_string());
+        } else {
+            shader_defs.remove(TEXTURE_SHADER_DEF);
+        }
+    }
+}
+
+/// Pipeline drawing the quads of shapes. Both sides of a quad are drawn, as screen space
+/// coordinates, negative scales and mirrored entities reverse the winding of its triangles.
+pub(crate) fn shape_pipeline(shader_stages: ShaderStages) -> PipelineDescriptor {
+    let mut pipeline_descriptor = PipelineDescriptor::default_config(shader_stages);
+    if let Some(rasterization_state) = pipeline_descriptor.rasterization_state.as_mut() {
+        rasterization_state.cull_mode = CullMode::None;
+    }
+    pipeline_descriptor
+}
+
+pub fn add_render_graph(resources: &Resources) {
+        let mut render_graph = resources.get_mut::<RenderGraph>().unwrap();
+
+        let resource_node = RenderResourcesNode::<ShapeTexture>::new(true);
+        render_graph.add_system_node(SHAPE_TEXTURE_NODE, resource_node);
+        render_graph.add_node_edge(SHAPE_TEXTURE_NODE, base::node::MAIN_PASS).unwrap();
+}
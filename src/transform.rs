@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+/// Builds a 2d affine transformation, scaling first, then rotating and translating.
+pub(crate) fn affine(scale: Vec2, rotation: f32, translation: Vec2) -> Mat3 {
+    let (sin, cos) = rotation.sin_cos();
+
+    Mat3::from_cols(
+        Vec3::new(cos * scale.x(), sin * scale.x(), 0.),
+        Vec3::new(-sin * scale.y(), cos * scale.y(), 0.),
+        Vec3::new(translation.x(), translation.y(), 1.),
+    )
+}
+
+/// Converts a 2d affine transformation into a 3d one placed at `z`.
+pub(crate) fn affine_to_mat4(affine: &Mat3, z: f32) -> Mat4 {
+    let x_axis = affine.x_axis();
+    let y_axis = affine.y_axis();
+    let translation = affine.z_axis();
+
+    Mat4::from_cols(
+        Vec4::new(x_axis.x(), x_axis.y(), 0., 0.),
+        Vec4::new(y_axis.x(), y_axis.y(), 0., 0.),
+        Vec4::new(0., 0., 1., 0.),
+        Vec4::new(translation.x(), translation.y(), z, 1.),
+    )
+}
+
+pub(crate) fn transform_point(affine: &Mat3, point: Vec2) -> Vec2 {
+    (*affine * point.extend(1.)).truncate()
+}
+
+/// Average factor lengths are scaled by, used for strokes and font sizes.
+pub(crate) fn scale_factor(affine: &Mat3) -> f32 {
+    affine.determinant().abs().sqrt()
+}
@@ -0,0 +1,62 @@
+//! Golden images of shapes rendered with the cpu rasterizer, stored in `snapshots/`.
+//! They are produced with `UPDATE_SNAPSHOTS=1 cargo test`; run it again after an intended change.
+
+use bevy::prelude::*;
+use bevy_prototype_2d_graphics::{assert_snapshot, Bounds, Immediate2DGraphics};
+
+#[test]
+fn rectangle_border() {
+    let mut graphics = Immediate2DGraphics::default();
+    // the border is scaled on the longer side, so it is as wide as it is high.
+    graphics.fill_rectangle(0., 0.)
+        .with_width(200.)
+        .with_height(100.)
+        .with_color(Color::WHITE)
+        .with_border(Color::RED, 0.2);
+
+    assert_snapshot!(graphics, "rectangle_border");
+}
+
+#[test]
+fn circle_border() {
+    let mut graphics = Immediate2DGraphics::default();
+    graphics.fill_circle(0., 0.)
+        .with_radius(100.)
+        .with_color(Color::BLUE)
+        .with_border(Color::RED, 0.3);
+
+    assert_snapshot!(graphics, "circle_border");
+}
+
+#[test]
+fn outlines() {
+    let mut graphics = Immediate2DGraphics::default();
+    graphics.stroke_circle(-120., 0.)
+        .with_radius(80.)
+        .with_stroke(10.)
+        .with_color(Color::GREEN);
+    graphics.stroke_rectangle(120., 0.)
+        .with_width(160.)
+        .with_height(100.)
+        .with_stroke(10.)
+        .with_color(Color::BLUE);
+
+    assert_snapshot!(graphics, "outlines");
+}
+
+#[test]
+fn clip_rect() {
+    let mut graphics = Immediate2DGraphics::default();
+    graphics.push_clip_rect(Bounds::new(Vec2::new(-60., -100.), Vec2::new(100., 40.)));
+    graphics.fill_circle(0., 0.)
+        .with_radius(100.)
+        .with_color(Color::GREEN);
+    graphics.pop_clip();
+
+    graphics.fill_rectangle(-150., 150.)
+        .with_width(60.)
+        .with_height(60.)
+        .with_color(Color::BLUE);
+
+    assert_snapshot!(graphics, "clip_rect");
+}